@@ -0,0 +1,487 @@
+use crate::{
+    cli::{Command, Script},
+    manifest::{Manifest, MANIFEST},
+    require::resolve,
+    storage::data_dir,
+    translation::Options,
+};
+use luna_rs::{compile_str, luna_impl::position::Located};
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap},
+    env,
+    error::Error,
+    fmt::Display,
+    fs::{self, File},
+    hash::{Hash, Hasher},
+    io::{self, Read, Seek, SeekFrom},
+    iter,
+    path::{Path, PathBuf},
+    process,
+};
+
+/// ends every bundled executable, right after the length of the payload
+const MAGIC: &[u8; 8] = b"DEIMOSB1";
+const TRAILER: usize = 8 + MAGIC.len();
+
+/// the files of a bundled game. luna has no bytecode format, so scripts are embedded as source,
+/// after checking that they compile
+#[derive(Debug, Clone, PartialEq, Hash)]
+pub struct Bundle {
+    /// names the save data of the game
+    pub name: String,
+    /// the script to run relative to the bundled directory, or empty if it holds a project
+    pub entry: String,
+    pub files: Vec<(String, Vec<u8>)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BundleError {
+    Corrupt,
+    Compile(String, String),
+    /// a script requires a module that can't be found, given as the script and the module path
+    MissingModule(String, String),
+    /// a file that would be bundled isn't inside the directory of the script or project
+    Outside(PathBuf),
+}
+impl Display for BundleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BundleError::Corrupt => write!(f, "the embedded game is corrupt"),
+            BundleError::Compile(path, err) => write!(f, "{path}:{err}"),
+            BundleError::MissingModule(path, module) => {
+                write!(f, "{path}: couldn't find the required module {module:?}")
+            }
+            BundleError::Outside(path) => {
+                write!(f, "{} is outside of the bundled directory", path.display())
+            }
+        }
+    }
+}
+impl Error for BundleError {}
+
+impl Bundle {
+    /// collects the script `input`, or the manifest and entry of the project directory `input`,
+    /// along with the modules required by string literal and the files of the asset directories.
+    /// a project's own asset directory comes along without being listed in `assets`
+    pub fn collect(input: &Path, assets: &[PathBuf]) -> Result<Self, Box<dyn Error>> {
+        let dir = if input.is_dir() {
+            input
+        } else {
+            input.parent().unwrap_or(Path::new(""))
+        };
+        let root = non_empty(dir).canonicalize()?;
+        let mut files = BTreeMap::new();
+        let mut asset_dirs = assets.to_vec();
+        let (name, entry, script, search_paths) = if input.is_dir() {
+            let manifest = Manifest::load(dir)?;
+            let path = dir.join(MANIFEST);
+            files.insert(relative(&root, &path)?, fs::read(&path)?);
+            asset_dirs.extend(manifest.assets);
+            (manifest.name, String::new(), manifest.entry, manifest.search_paths)
+        } else {
+            // the game is extracted to a directory with another name, so its name has to come along
+            let name = input.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+            (name, relative(&root, input)?, input.to_path_buf(), vec![])
+        };
+        collect_scripts(&root, &script, &search_paths, &mut files)?;
+        for assets in asset_dirs {
+            let prefix = relative(&root, &assets)?;
+            let prefix = if prefix.is_empty() {
+                prefix
+            } else {
+                format!("{prefix}/")
+            };
+            collect_dir(&assets, &prefix, &mut files)?;
+        }
+        Ok(Self {
+            name,
+            entry,
+            files: files.into_iter().collect(),
+        })
+    }
+    /// writes a copy of the running executable with the bundle appended to `output`
+    pub fn write(&self, output: &Path) -> Result<(), Box<dyn Error>> {
+        let exe = env::current_exe()?;
+        let mut data = fs::read(&exe)?;
+        // bundling from a bundled game shouldn't carry its game along
+        if let Some(start) = payload_start(&data) {
+            data.truncate(start);
+        }
+        data.extend(self.payload());
+        fs::write(output, data)?;
+        fs::set_permissions(output, fs::metadata(exe)?.permissions())?;
+        Ok(())
+    }
+    /// the bundle as it's appended to an executable, followed by its length and `MAGIC`
+    fn payload(&self) -> Vec<u8> {
+        let mut data = vec![];
+        push_string(&mut data, &self.name);
+        push_string(&mut data, &self.entry);
+        data.extend((self.files.len() as u64).to_le_bytes());
+        for (path, content) in self.files.iter() {
+            push_string(&mut data, path);
+            data.extend((content.len() as u64).to_le_bytes());
+            data.extend(content);
+        }
+        data.extend((data.len() as u64).to_le_bytes());
+        data.extend(MAGIC);
+        data
+    }
+    /// the bundle appended to the running executable, if there is one
+    pub fn embedded() -> Result<Option<Self>, Box<dyn Error>> {
+        let mut file = File::open(env::current_exe()?)?;
+        let len = file.metadata()?.len();
+        if len < TRAILER as u64 {
+            return Ok(None);
+        }
+        let mut trailer = [0; TRAILER];
+        file.seek(SeekFrom::End(-(TRAILER as i64)))?;
+        file.read_exact(&mut trailer)?;
+        if &trailer[8..] != MAGIC {
+            return Ok(None);
+        }
+        let size = u64::from_le_bytes(trailer[..8].try_into()?);
+        let start = (len - TRAILER as u64)
+            .checked_sub(size)
+            .ok_or(BundleError::Corrupt)?;
+        let mut payload = vec![0; size as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut payload)?;
+        Self::parse(&payload)
+            .map(Some)
+            .ok_or(BundleError::Corrupt.into())
+    }
+    fn parse(mut payload: &[u8]) -> Option<Self> {
+        let name = take_string(&mut payload)?;
+        let entry = take_string(&mut payload)?;
+        let count = take_u64(&mut payload)?;
+        let mut files = vec![];
+        for _ in 0..count {
+            let path = take_string(&mut payload)?;
+            let len = take_u64(&mut payload)?.try_into().ok()?;
+            files.push((path, take(&mut payload, len)?.to_vec()));
+        }
+        Some(Self { name, entry, files })
+    }
+    /// writes the files into a directory of the user's data dir that only they can get into,
+    /// named after the content so the same game is only extracted once, and returns it.
+    /// a directory left by an earlier run is only reused if it holds exactly the bundled files
+    pub fn extract(&self) -> Result<PathBuf, Box<dyn Error>> {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        let bundles = data_dir()
+            .ok_or("couldn't find a directory for user data to extract the game to")?
+            .join("deimos")
+            // app names can't start with a dot, so this never holds save data
+            .join(".bundles");
+        create_private_dir(&bundles, true)?;
+        let dir = bundles.join(format!("{:016x}", hasher.finish()));
+        if self.matches(&dir) {
+            return Ok(dir);
+        }
+        // extract next to the final directory, so a half extracted game is never run
+        let partial = dir.with_extension(process::id().to_string());
+        if partial.exists() {
+            fs::remove_dir_all(&partial)?;
+        }
+        create_private_dir(&partial, false)?;
+        for (path, content) in self.files.iter() {
+            let path = partial.join(path);
+            if let Some(parent) = path.parent() {
+                create_private_dir(parent, true)?;
+            }
+            fs::write(path, content)?;
+        }
+        if dir.exists() {
+            fs::remove_dir_all(&dir)?;
+        }
+        if fs::rename(&partial, &dir).is_err() {
+            // another instance of the game won the race
+            fs::remove_dir_all(&partial)?;
+            if !self.matches(&dir) {
+                return Err(BundleError::Corrupt.into());
+            }
+        }
+        Ok(dir)
+    }
+    /// whether `dir` holds the files of the bundle and nothing else
+    fn matches(&self, dir: &Path) -> bool {
+        let mut found = BTreeMap::new();
+        if !dir.is_dir() || collect_dir(dir, "", &mut found).is_err() {
+            return false;
+        }
+        let expected = self
+            .files
+            .iter()
+            .map(|(path, content)| (path.as_str(), content.as_slice()))
+            .collect::<BTreeMap<_, _>>();
+        found
+            .iter()
+            .map(|(path, content)| (path.as_str(), content.as_slice()))
+            .eq(expected)
+    }
+    /// extracts the game and returns the command running it with the arguments the executable got
+    pub fn command(&self) -> Result<Command, Box<dyn Error>> {
+        let dir = self.extract()?;
+        let script = if self.entry.is_empty() {
+            Script::Project(dir.display().to_string())
+        } else {
+            Script::File(dir.join(&self.entry).display().to_string())
+        };
+        Ok(Command::Run {
+            script,
+            headless: false,
+            watch: false,
+            // players have no terminal to read errors in
+            overlay: true,
+            options: Options {
+                args: env::args().skip(1).collect(),
+                app: Some(self.name.clone()),
+                ..Default::default()
+            },
+        })
+    }
+}
+
+/// adds `entry` and every module it requires, directly or through other modules, checking that
+/// each compiles
+fn collect_scripts(
+    root: &Path,
+    entry: &Path,
+    search_paths: &[PathBuf],
+    files: &mut BTreeMap<String, Vec<u8>>,
+) -> Result<(), Box<dyn Error>> {
+    let mut pending = vec![entry.to_path_buf()];
+    while let Some(path) = pending.pop() {
+        let name = relative(root, &path)?;
+        if files.contains_key(&name) {
+            continue;
+        }
+        let data = fs::read(&path)?;
+        let text = String::from_utf8_lossy(&data);
+        compile_str(&text).map_err(|Located { value: err, pos }| {
+            BundleError::Compile(
+                name.clone(),
+                format!("{}:{}: {err}", pos.ln.start + 1, pos.col.start + 1),
+            )
+        })?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for module in required(&text) {
+            let file = iter::once(dir)
+                .chain(search_paths.iter().map(PathBuf::as_path))
+                .find_map(|dir| resolve(dir, &module))
+                .ok_or_else(|| BundleError::MissingModule(name.clone(), module))?;
+            pending.push(file);
+        }
+        files.insert(name, data);
+    }
+    Ok(())
+}
+/// the paths passed to `require` as string literals outside of comments. modules required by a
+/// computed path can't be found this way, so they have to be in an asset directory
+fn required(text: &str) -> Vec<String> {
+    let mut paths = vec![];
+    for line in text.lines() {
+        let code = line.split('#').next().unwrap_or_default();
+        required_in(code, &mut paths);
+    }
+    paths
+}
+fn required_in(mut rest: &str, paths: &mut Vec<String>) {
+    while let Some(start) = rest.find("require") {
+        rest = rest[start + "require".len()..].trim_start();
+        let Some(args) = rest.strip_prefix('(') else {
+            continue;
+        };
+        let Some(literal) = args.trim_start().strip_prefix('"') else {
+            continue;
+        };
+        if let Some(end) = literal.find('"') {
+            paths.push(literal[..end].to_string());
+        }
+    }
+}
+/// adds every file under `dir` with its path prefixed by `prefix`, leaving out hidden ones
+fn collect_dir(
+    dir: &Path,
+    prefix: &str,
+    files: &mut BTreeMap<String, Vec<u8>>,
+) -> Result<(), Box<dyn Error>> {
+    let mut entries = fs::read_dir(non_empty(dir))?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') {
+            continue;
+        }
+        let path = format!("{prefix}{name}");
+        if entry.file_type()?.is_dir() {
+            collect_dir(&entry.path(), &format!("{path}/"), files)?;
+        } else {
+            let data = fs::read(entry.path())?;
+            // earlier bundles of the game, which are often written next to it
+            if payload_start(&data).is_none() {
+                files.insert(path, data);
+            }
+        }
+    }
+    Ok(())
+}
+/// `path` relative to `root`, with the names joined by `/` as they are in the payload
+fn relative(root: &Path, path: &Path) -> Result<String, Box<dyn Error>> {
+    let path = non_empty(path).canonicalize()?;
+    let relative = path
+        .strip_prefix(root)
+        .map_err(|_| BundleError::Outside(path.clone()))?;
+    Ok(relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/"))
+}
+/// `.` for the empty path a file name without a directory has as its parent
+fn non_empty(path: &Path) -> &Path {
+    if path.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        path
+    }
+}
+/// creates `dir` so that only the current user can read or write it
+fn create_private_dir(dir: &Path, recursive: bool) -> io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(recursive);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(dir)
+}
+/// where the payload starts in a bundled executable
+fn payload_start(data: &[u8]) -> Option<usize> {
+    let trailer = data.len().checked_sub(TRAILER)?;
+    if &data[trailer + 8..] != MAGIC {
+        return None;
+    }
+    let size = u64::from_le_bytes(data[trailer..trailer + 8].try_into().ok()?);
+    trailer.checked_sub(size.try_into().ok()?)
+}
+fn push_string(data: &mut Vec<u8>, string: &str) {
+    data.extend((string.len() as u64).to_le_bytes());
+    data.extend(string.as_bytes());
+}
+fn take<'a>(payload: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if payload.len() < len {
+        return None;
+    }
+    let (taken, rest) = payload.split_at(len);
+    *payload = rest;
+    Some(taken)
+}
+fn take_u64(payload: &mut &[u8]) -> Option<u64> {
+    Some(u64::from_le_bytes(take(payload, 8)?.try_into().ok()?))
+}
+fn take_string(payload: &mut &[u8]) -> Option<String> {
+    let len = take_u64(payload)?.try_into().ok()?;
+    String::from_utf8(take(payload, len)?.to_vec()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    fn bundle() -> Bundle {
+        Bundle {
+            name: "game".into(),
+            entry: "main.luna".into(),
+            files: vec![
+                ("assets/tile.png".into(), vec![0, 1, 2, 255]),
+                ("main.luna".into(), b"print(1)".to_vec()),
+            ],
+        }
+    }
+    #[test]
+    fn payload_round_trip() {
+        let bundle = bundle();
+        let exe = b"\x7fELF not really an executable".to_vec();
+        let mut data = exe.clone();
+        data.extend(bundle.payload());
+
+        let start = payload_start(&data).unwrap();
+        assert_eq!(start, exe.len());
+        let payload = &data[start..data.len() - TRAILER];
+        assert_eq!(Bundle::parse(payload), Some(bundle));
+        assert_eq!(payload_start(&exe), None);
+    }
+
+    #[test]
+    fn truncated_payload() {
+        let payload = bundle().payload();
+        let payload = &payload[..payload.len() - TRAILER];
+        for len in 0..payload.len() {
+            assert_eq!(Bundle::parse(&payload[..len]), None, "cut after {len} bytes");
+        }
+    }
+
+    #[test]
+    fn finds_literal_requires() {
+        let text = r#"
+            let vec = require("lib/vec")
+            let util = require ( "util" ) # require("commented")
+            # require("commented")
+            let name = "dynamic"
+            let dynamic = require(name)
+        "#;
+        assert_eq!(required(text), ["lib/vec", "util"]);
+    }
+
+    #[test]
+    fn collects_only_what_the_script_needs() {
+        let dir = TempDir::new("collect");
+        dir.write("main.luna", "let util = require(\"lib/util\")\nprint(util)");
+        dir.write("lib/util.luna", "return require(\"../shared\")");
+        dir.write("shared.luna", "return 1");
+        dir.write("assets/tile.png", "png");
+        dir.write("notes.txt", "private");
+        dir.write("lib/unused.luna", "return 2");
+
+        let bundle = Bundle::collect(&dir.path().join("main.luna"), &[dir.path().join("assets")]).unwrap();
+        assert_eq!(bundle.name, "main");
+        assert_eq!(bundle.entry, "main.luna");
+        let paths = bundle.files.iter().map(|(path, _)| path.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            ["assets/tile.png", "lib/util.luna", "main.luna", "shared.luna"]
+        );
+    }
+
+    #[test]
+    fn rejects_missing_and_outside_modules() {
+        let dir = TempDir::new("reject");
+        dir.write("game/main.luna", "require(\"missing\")");
+        let err = Bundle::collect(&dir.path().join("game/main.luna"), &[]).unwrap_err();
+        assert!(err.to_string().contains("missing"), "{err}");
+
+        dir.write("game/main.luna", "require(\"../outside\")");
+        dir.write("outside.luna", "return 1");
+        let err = Bundle::collect(&dir.path().join("game/main.luna"), &[]).unwrap_err();
+        assert!(err.to_string().contains("outside of the bundled directory"), "{err}");
+    }
+
+    #[test]
+    fn matches_only_the_exact_files() {
+        let bundle = bundle();
+        let dir = TempDir::new("matches");
+        assert!(!bundle.matches(&dir.path().join("missing")));
+        for (path, content) in bundle.files.iter() {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        assert!(bundle.matches(dir.path()));
+        dir.write("main.luna", "print(2)");
+        assert!(!bundle.matches(dir.path()));
+        dir.write("main.luna", "print(1)");
+        dir.write("extra.luna", "print(3)");
+        assert!(!bundle.matches(dir.path()));
+    }
+}
@@ -0,0 +1,309 @@
+use crate::{golden::TestConfig, manifest::dir_name, translation::Options};
+use std::{error::Error, fmt::Display, iter::Peekable, path::Path};
+
+#[derive(Debug, Clone)]
+pub enum Command {
+    Run {
+        script: Script,
+        headless: bool,
+        watch: bool,
+        /// keep the window open and show runtime errors in it
+        overlay: bool,
+        options: Options,
+    },
+    Repl {
+        headless: bool,
+        options: Options,
+    },
+    Test {
+        dir: String,
+        config: TestConfig,
+    },
+    Bundle {
+        /// a script or a project directory
+        input: String,
+        output: String,
+        /// directories bundled next to the scripts
+        assets: Vec<String>,
+    },
+    Help,
+    Version,
+}
+#[derive(Debug, Clone, PartialEq)]
+pub enum Script {
+    File(String),
+    /// a directory with a `deimos.toml`, which names the file to run
+    Project(String),
+    Stdin,
+    Inline(String),
+}
+#[derive(Debug, Clone, PartialEq)]
+pub enum CliError {
+    UnknownFlag(String),
+    ExpectedValue(&'static str, String),
+    InvalidNumber(String, String),
+    ExpectedDir,
+    WatchWithoutFile,
+    ExpectedInput,
+}
+impl Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliError::UnknownFlag(flag) => write!(f, "unknown flag {flag}"),
+            CliError::ExpectedValue(expected, flag) => write!(f, "expected {expected} after {flag}"),
+            CliError::InvalidNumber(flag, value) => write!(f, "invalid number {value:?} for {flag}"),
+            CliError::ExpectedDir => write!(f, "expected a directory to test"),
+            CliError::WatchWithoutFile => write!(f, "--watch needs a script file"),
+            CliError::ExpectedInput => write!(f, "expected a script or project directory to bundle"),
+        }
+    }
+}
+impl Error for CliError {}
+
+impl Script {
+    /// the name errors in the script are reported with
+    pub fn name(&self) -> &str {
+        match self {
+            Script::File(path) | Script::Project(path) => path,
+            Script::Stdin => "<stdin>",
+            Script::Inline(_) => "<inline>",
+        }
+    }
+}
+
+pub fn parse<I: Iterator<Item = String>>(args: I) -> Result<Command, CliError> {
+    let mut args = args.peekable();
+    if args.next_if(|arg| arg == "test").is_some() {
+        return parse_test(args);
+    }
+    if args.next_if(|arg| arg == "bundle").is_some() {
+        return parse_bundle(args);
+    }
+    let project = args.next_if(|arg| arg == "run").is_some();
+    let mut headless = false;
+    let mut watch = false;
+    let mut overlay = false;
+    let mut options = Options::default();
+    let script = loop {
+        let Some(arg) = args.next() else {
+            if project {
+                break Script::Project(".".to_string());
+            }
+            return Ok(Command::Repl { headless, options });
+        };
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-V" | "--version" => return Ok(Command::Version),
+            "--headless" => headless = true,
+            "--watch" => watch = true,
+            "--error-overlay" => overlay = true,
+            "--replay" => options.replay = Some(value(&mut args, "a file", arg)?),
+            "--record" => options.record = Some(value(&mut args, "a file", arg)?),
+            _ if arg.starts_with("--allow-fs=") => {
+                options.allow_fs = Some(arg["--allow-fs=".len()..].into());
+            }
+            "-e" if !project => break Script::Inline(value(&mut args, "some code", arg)?),
+            "-" if !project => break Script::Stdin,
+            // `deimos run -- args` runs the project in the current directory
+            "--" if project => break Script::Project(".".to_string()),
+            _ if arg.starts_with('-') => return Err(CliError::UnknownFlag(arg)),
+            _ if project => break Script::Project(arg),
+            _ => break Script::File(arg),
+        }
+    };
+    if watch && !matches!(script, Script::File(_) | Script::Project(_)) {
+        return Err(CliError::WatchWithoutFile);
+    }
+    // everything after the script belongs to it, optionally separated by `--`
+    args.next_if(|arg| arg == "--");
+    options.args = args.collect();
+    Ok(Command::Run {
+        script,
+        headless,
+        watch,
+        overlay,
+        options,
+    })
+}
+fn parse_test<I: Iterator<Item = String>>(mut args: Peekable<I>) -> Result<Command, CliError> {
+    let mut config = TestConfig::default();
+    while let Some(flag) = args.next_if(|arg| arg.starts_with('-')) {
+        match flag.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--update" => config.update = true,
+            "--frames" => config.frames = number(&mut args, flag)?,
+            "--tolerance" => config.tolerance = number(&mut args, flag)?,
+            _ => return Err(CliError::UnknownFlag(flag)),
+        }
+    }
+    let dir = args.next().ok_or(CliError::ExpectedDir)?;
+    Ok(Command::Test { dir, config })
+}
+fn parse_bundle<I: Iterator<Item = String>>(mut args: Peekable<I>) -> Result<Command, CliError> {
+    let mut input = None;
+    let mut output = None;
+    let mut assets = vec![];
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-o" | "--output" => output = Some(value(&mut args, "a file", arg)?),
+            "--assets" => assets.push(value(&mut args, "a directory", arg)?),
+            _ if arg.starts_with('-') => return Err(CliError::UnknownFlag(arg)),
+            _ => input = Some(arg),
+        }
+    }
+    let input = input.ok_or(CliError::ExpectedInput)?;
+    // `game.luna` and `game/` both become `game`
+    let output = output.unwrap_or_else(|| {
+        let path = Path::new(&input);
+        match path.file_stem() {
+            Some(stem) if !path.is_dir() => stem.to_string_lossy().into_owned(),
+            _ => dir_name(path),
+        }
+    });
+    Ok(Command::Bundle {
+        input,
+        output,
+        assets,
+    })
+}
+fn value<I: Iterator<Item = String>>(
+    args: &mut I,
+    expected: &'static str,
+    flag: String,
+) -> Result<String, CliError> {
+    args.next().ok_or(CliError::ExpectedValue(expected, flag))
+}
+fn number<I: Iterator<Item = String>, N: std::str::FromStr>(
+    args: &mut I,
+    flag: String,
+) -> Result<N, CliError> {
+    let value = args
+        .next()
+        .ok_or_else(|| CliError::ExpectedValue("a number", flag.clone()))?;
+    value.parse().map_err(|_| CliError::InvalidNumber(flag, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &[&str]) -> Result<Command, CliError> {
+        parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn passes_the_rest_to_the_script() {
+        let Ok(Command::Run {
+            script, options, ..
+        }) = parse_args(&["--headless", "game.luna", "--", "a", "--b"])
+        else {
+            panic!("expected a run");
+        };
+        assert_eq!(script, Script::File("game.luna".into()));
+        assert_eq!(options.args, ["a", "--b"]);
+
+        let Ok(Command::Run { options, .. }) = parse_args(&["game.luna", "a", "b"]) else {
+            panic!("expected a run");
+        };
+        assert_eq!(options.args, ["a", "b"]);
+    }
+
+    #[test]
+    fn reads_flags_before_the_script() {
+        let Ok(Command::Run {
+            script,
+            headless,
+            watch,
+            overlay,
+            options,
+        }) = parse_args(&[
+            "--headless",
+            "--watch",
+            "--error-overlay",
+            "--replay",
+            "in.json",
+            "--record",
+            "out.json",
+            "--allow-fs=data",
+            "game.luna",
+        ])
+        else {
+            panic!("expected a run");
+        };
+        assert_eq!(script, Script::File("game.luna".into()));
+        assert!(headless && watch && overlay);
+        assert_eq!(options.replay.as_deref(), Some("in.json"));
+        assert_eq!(options.record.as_deref(), Some("out.json"));
+        assert_eq!(options.allow_fs, Some("data".into()));
+    }
+
+    #[test]
+    fn inline_code_and_stdin() {
+        let Ok(Command::Run { script, options, .. }) = parse_args(&["-e", "print(1)", "x"]) else {
+            panic!("expected a run");
+        };
+        assert_eq!(script, Script::Inline("print(1)".into()));
+        assert_eq!(options.args, ["x"]);
+        assert!(matches!(
+            parse_args(&["-"]),
+            Ok(Command::Run {
+                script: Script::Stdin,
+                ..
+            })
+        ));
+        assert_eq!(
+            parse_args(&["-e"]).err(),
+            Some(CliError::ExpectedValue("some code", "-e".into()))
+        );
+    }
+
+    #[test]
+    fn subcommands() {
+        assert!(matches!(parse_args(&[]), Ok(Command::Repl { .. })));
+        assert!(matches!(parse_args(&["--help"]), Ok(Command::Help)));
+        assert!(matches!(parse_args(&["-V"]), Ok(Command::Version)));
+        assert!(matches!(
+            parse_args(&["run"]),
+            Ok(Command::Run {
+                script: Script::Project(dir),
+                ..
+            }) if dir == "."
+        ));
+        let Ok(Command::Test { dir, config }) =
+            parse_args(&["test", "--frames", "10", "--tolerance", "2", "examples"])
+        else {
+            panic!("expected a test run");
+        };
+        assert_eq!(dir, "examples");
+        assert_eq!((config.frames, config.tolerance, config.update), (10, 2, false));
+        let Ok(Command::Bundle {
+            input,
+            output,
+            assets,
+        }) = parse_args(&["bundle", "game.luna", "--assets", "images"])
+        else {
+            panic!("expected a bundle");
+        };
+        assert_eq!((input.as_str(), output.as_str()), ("game.luna", "game"));
+        assert_eq!(assets, ["images"]);
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert_eq!(
+            parse_args(&["--nope"]).err(),
+            Some(CliError::UnknownFlag("--nope".into()))
+        );
+        assert_eq!(
+            parse_args(&["--watch", "-e", "1"]).err(),
+            Some(CliError::WatchWithoutFile)
+        );
+        assert_eq!(
+            parse_args(&["test", "--frames", "many", "dir"]).err(),
+            Some(CliError::InvalidNumber("--frames".into(), "many".into()))
+        );
+        assert_eq!(parse_args(&["test"]).err(), Some(CliError::ExpectedDir));
+        assert_eq!(parse_args(&["bundle"]).err(), Some(CliError::ExpectedInput));
+    }
+}
@@ -0,0 +1,301 @@
+use luna_rs::{
+    lang::value::{FunctionKind, Object, Value},
+    object, option, typed, ExpectedType, ExpectedTypes,
+};
+use sdl2::pixels::Color;
+use std::{cell::RefCell, collections::HashMap, error::Error, fmt::Display, rc::Rc};
+
+/// the css color keywords scripts are most likely to reach for
+const NAMED: &[(&str, Color)] = &[
+    ("black", Color::RGBA(0, 0, 0, 255)),
+    ("white", Color::RGBA(255, 255, 255, 255)),
+    ("gray", Color::RGBA(128, 128, 128, 255)),
+    ("grey", Color::RGBA(128, 128, 128, 255)),
+    ("silver", Color::RGBA(192, 192, 192, 255)),
+    ("red", Color::RGBA(255, 0, 0, 255)),
+    ("maroon", Color::RGBA(128, 0, 0, 255)),
+    ("orange", Color::RGBA(255, 165, 0, 255)),
+    ("yellow", Color::RGBA(255, 255, 0, 255)),
+    ("olive", Color::RGBA(128, 128, 0, 255)),
+    ("lime", Color::RGBA(0, 255, 0, 255)),
+    ("green", Color::RGBA(0, 128, 0, 255)),
+    ("teal", Color::RGBA(0, 128, 128, 255)),
+    ("cyan", Color::RGBA(0, 255, 255, 255)),
+    ("aqua", Color::RGBA(0, 255, 255, 255)),
+    ("blue", Color::RGBA(0, 0, 255, 255)),
+    ("navy", Color::RGBA(0, 0, 128, 255)),
+    ("purple", Color::RGBA(128, 0, 128, 255)),
+    ("magenta", Color::RGBA(255, 0, 255, 255)),
+    ("fuchsia", Color::RGBA(255, 0, 255, 255)),
+    ("pink", Color::RGBA(255, 192, 203, 255)),
+    ("brown", Color::RGBA(165, 42, 42, 255)),
+    ("transparent", Color::RGBA(0, 0, 0, 0)),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColorError {
+    Unknown(String),
+    /// a color object without an int or float `r`, `g` or `b`
+    MissingChannel(&'static str),
+}
+impl Display for ColorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColorError::Unknown(name) => write!(f, "unknown color {name:?}"),
+            ColorError::MissingChannel(channel) => {
+                write!(f, "expected int/float for the {channel} of the color")
+            }
+        }
+    }
+}
+impl Error for ColorError {}
+
+/// parses `#rgb`, `#rgba`, `#rrggbb`, `#rrggbbaa` or a color name
+pub fn parse(text: &str) -> Result<Color, ColorError> {
+    let unknown = || ColorError::Unknown(text.to_string());
+    if let Some(hex) = text.strip_prefix('#') {
+        // `from_str_radix` would take a leading `+` too
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(unknown());
+        }
+        let digits = match hex.len() {
+            // every digit of the short forms stands for two
+            3 | 4 => hex.chars().map(|c| c.to_string().repeat(2)).collect::<Vec<_>>(),
+            6 | 8 => (0..hex.len()).step_by(2).map(|i| hex[i..i + 2].to_string()).collect(),
+            _ => return Err(unknown()),
+        };
+        let channels = digits
+            .iter()
+            .map(|digits| u8::from_str_radix(digits, 16))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| unknown())?;
+        return Ok(Color::RGBA(
+            channels[0],
+            channels[1],
+            channels[2],
+            channels.get(3).copied().unwrap_or(255),
+        ));
+    }
+    let name = text.to_lowercase();
+    NAMED
+        .iter()
+        .find(|(other, _)| *other == name)
+        .map(|(_, color)| *color)
+        .ok_or_else(unknown)
+}
+/// reads a color from a string or an object with `r`, `g`, `b` and an optional `a`
+pub fn from_value(value: &Value) -> Result<Option<Color>, ColorError> {
+    match value {
+        Value::String(text) => parse(text).map(Some),
+        Value::Object(object) => {
+            let object = object.borrow();
+            let channel = |name: &'static str| match object.get(name) {
+                Some(Value::Int(v)) => Some(v.clamp(0, 255) as u8),
+                Some(Value::Float(v)) => Some(v.round().clamp(0., 255.) as u8),
+                _ => None,
+            };
+            let missing = ColorError::MissingChannel;
+            Ok(Some(Color::RGBA(
+                channel("r").ok_or(missing("r"))?,
+                channel("g").ok_or(missing("g"))?,
+                channel("b").ok_or(missing("b"))?,
+                channel("a").unwrap_or(255),
+            )))
+        }
+        _ => Ok(None),
+    }
+}
+pub fn to_value(color: Color) -> Value {
+    object! {
+        "r" = i64::from(color.r),
+        "g" = i64::from(color.g),
+        "b" = i64::from(color.b),
+        "a" = i64::from(color.a)
+    }
+}
+/// the arguments of every function that takes a color: `r, g, b, [a]`, a string or a color object
+pub fn from_args(args: Vec<Value>) -> Result<Color, Box<dyn Error>> {
+    let mut args = args.into_iter().enumerate();
+    match args.next() {
+        Some((_, Value::Int(r))) => {
+            let r = r.clamp(0, 255) as u8;
+            let g = typed!(args: Int).clamp(0, 255) as u8;
+            let b = typed!(args: Int).clamp(0, 255) as u8;
+            let a = typed!(args: Int? int => int.clamp(0, 255) as u8);
+            Ok(Color::RGBA(r, g, b, a.unwrap_or(255)))
+        }
+        Some((idx, value)) => from_value(&value)?.ok_or_else(|| {
+            ExpectedTypes {
+                idx,
+                expected: vec!["int", "string", "object"],
+                got: value.typ(),
+            }
+            .into()
+        }),
+        None => Err(ExpectedTypes {
+            idx: 0,
+            expected: vec!["int", "string", "object"],
+            got: "null",
+        }
+        .into()),
+    }
+}
+
+/// `h` in degrees, `s` and `v` from 0 to 1
+pub fn hsv(h: f64, s: f64, v: f64) -> (f64, f64, f64) {
+    let h = h.rem_euclid(360.) / 60.;
+    let (s, v) = (s.clamp(0., 1.), v.clamp(0., 1.));
+    let c = v * s;
+    let x = c * (1. - (h % 2. - 1.).abs());
+    let (r, g, b) = match h as u8 {
+        0 => (c, x, 0.),
+        1 => (x, c, 0.),
+        2 => (0., c, x),
+        3 => (0., x, c),
+        4 => (x, 0., c),
+        _ => (c, 0., x),
+    };
+    let m = v - c;
+    (r + m, g + m, b + m)
+}
+pub fn lerp(a: Color, b: Color, t: f64) -> Color {
+    let t = t.clamp(0., 1.);
+    let mix = |a: u8, b: u8| (f64::from(a) + (f64::from(b) - f64::from(a)) * t).round() as u8;
+    Color::RGBA(mix(a.r, b.r), mix(a.g, b.g), mix(a.b, b.b), mix(a.a, b.a))
+}
+
+/// the `color` global
+pub fn color_module() -> Value {
+    object! {
+        "rgba" = Value::Function(FunctionKind::UserFunction(Rc::new(|_, args| {
+            Ok(to_value(from_args(args)?))
+        }))),
+        "hsv" = Value::Function(FunctionKind::UserFunction(Rc::new(|_, args| {
+            let mut args = args.into_iter().enumerate();
+            let h = option!(args:
+                Int => int { int as f64 },
+                Float => float { float }
+            );
+            let s = option!(args:
+                Int => int { int as f64 },
+                Float => float { float }
+            );
+            let v = option!(args:
+                Int => int { int as f64 },
+                Float => float { float }
+            );
+            let a = typed!(args: Int? int => int.clamp(0, 255) as u8);
+            let (r, g, b) = hsv(h, s, v);
+            let channel = |v: f64| (v * 255.).round() as u8;
+            Ok(to_value(Color::RGBA(channel(r), channel(g), channel(b), a.unwrap_or(255))))
+        }))),
+        "lerp" = Value::Function(FunctionKind::UserFunction(Rc::new(|_, args| {
+            let mut args = args.into_iter().enumerate();
+            let mut color = || {
+                let (idx, value) = args.next().unwrap_or_default();
+                from_value(&value)?.ok_or_else(|| -> Box<dyn Error> {
+                    ExpectedTypes {
+                        idx,
+                        expected: vec!["string", "object"],
+                        got: value.typ(),
+                    }
+                    .into()
+                })
+            };
+            let a = color()?;
+            let b = color()?;
+            let t = option!(args:
+                Int => int { int as f64 },
+                Float => float { float }
+            );
+            Ok(to_value(lerp(a, b, t)))
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hex_colors() {
+        assert_eq!(parse("#f80"), Ok(Color::RGBA(255, 136, 0, 255)));
+        assert_eq!(parse("#f808"), Ok(Color::RGBA(255, 136, 0, 136)));
+        assert_eq!(parse("#12aBcD"), Ok(Color::RGBA(0x12, 0xab, 0xcd, 255)));
+        assert_eq!(parse("#12abcd80"), Ok(Color::RGBA(0x12, 0xab, 0xcd, 0x80)));
+        for text in ["#", "#12", "#12345", "#1234567", "#ggg", "#+f+f+f", "#ééé", "123"] {
+            assert_eq!(parse(text), Err(ColorError::Unknown(text.into())), "{text}");
+        }
+    }
+
+    #[test]
+    fn parses_named_colors() {
+        assert_eq!(parse("orange"), Ok(Color::RGBA(255, 165, 0, 255)));
+        assert_eq!(parse("Navy"), Ok(Color::RGBA(0, 0, 128, 255)));
+        assert_eq!(parse("transparent"), Ok(Color::RGBA(0, 0, 0, 0)));
+        assert_eq!(parse("blurple"), Err(ColorError::Unknown("blurple".into())));
+    }
+
+    #[test]
+    fn reads_color_values() {
+        assert_eq!(from_value(&"red".into()), Ok(Some(Color::RGBA(255, 0, 0, 255))));
+        let object = object! {
+            "r" = Value::Int(300),
+            "g" = Value::Float(127.6),
+            "b" = Value::Int(-4)
+        };
+        assert_eq!(from_value(&object), Ok(Some(Color::RGBA(255, 128, 0, 255))));
+        let object = object! { "r" = Value::Int(1), "b" = Value::Int(1) };
+        assert_eq!(from_value(&object), Err(ColorError::MissingChannel("g")));
+        assert_eq!(from_value(&Value::Int(1)), Ok(None));
+        let color = Color::RGBA(1, 2, 3, 4);
+        assert_eq!(from_value(&to_value(color)), Ok(Some(color)));
+    }
+
+    #[test]
+    fn reads_color_args() {
+        let args = |args: Vec<Value>| from_args(args).map_err(|err| err.to_string());
+        assert_eq!(
+            args(vec![Value::Int(1), Value::Int(2), Value::Int(3)]),
+            Ok(Color::RGBA(1, 2, 3, 255))
+        );
+        assert_eq!(
+            args(vec![Value::Int(1), Value::Int(2), Value::Int(3), Value::Int(999)]),
+            Ok(Color::RGBA(1, 2, 3, 255))
+        );
+        assert_eq!(args(vec!["#010203".into()]), Ok(Color::RGBA(1, 2, 3, 255)));
+        assert!(args(vec![Value::Int(1)]).is_err());
+        assert!(args(vec![Value::Bool(true)]).is_err());
+        assert!(args(vec![]).is_err());
+    }
+
+    #[test]
+    fn converts_hsv() {
+        let close = |(r, g, b): (f64, f64, f64), (er, eg, eb): (f64, f64, f64)| {
+            assert!(
+                (r - er).abs() < 1e-9 && (g - eg).abs() < 1e-9 && (b - eb).abs() < 1e-9,
+                "{:?} != {:?}",
+                (r, g, b),
+                (er, eg, eb)
+            );
+        };
+        close(hsv(0., 1., 1.), (1., 0., 0.));
+        close(hsv(120., 1., 1.), (0., 1., 0.));
+        close(hsv(240., 1., 0.5), (0., 0., 0.5));
+        close(hsv(60., 0.5, 1.), (1., 1., 0.5));
+        // hues wrap around and saturation and value are clamped
+        close(hsv(-120., 2., 1.), (0., 0., 1.));
+        close(hsv(720., 1., 1.), (1., 0., 0.));
+        close(hsv(300., 0., -1.), (0., 0., 0.));
+    }
+
+    #[test]
+    fn lerps_every_channel() {
+        let (a, b) = (Color::RGBA(0, 100, 200, 0), Color::RGBA(255, 0, 100, 255));
+        assert_eq!(lerp(a, b, 0.), a);
+        assert_eq!(lerp(a, b, 1.), b);
+        assert_eq!(lerp(a, b, 0.5), Color::RGBA(128, 50, 150, 128));
+        assert_eq!(lerp(a, b, -1.), a);
+        assert_eq!(lerp(a, b, 2.), b);
+    }
+}
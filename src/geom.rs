@@ -0,0 +1,588 @@
+use crate::translation::method_error;
+use luna_rs::{
+    lang::value::{FunctionKind, Object, UserObject, UserObjectError, Value},
+    luna_impl::interpreter::Interpreter,
+    object, option, typed, ExpectedType, ExpectedTypes,
+};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    error::Error,
+    fmt::Display,
+    ops::{Add, Sub},
+    rc::Rc,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GeomError {
+    /// a shape object without an int or float field it needs
+    MissingField {
+        shape: &'static str,
+        field: &'static str,
+    },
+}
+impl Display for GeomError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GeomError::MissingField { shape, field } => {
+                write!(f, "expected int/float for the {field} of the {shape}")
+            }
+        }
+    }
+}
+impl Error for GeomError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Vector {
+    pub x: f64,
+    pub y: f64,
+}
+impl Vector {
+    pub fn new(x: f64, y: f64) -> Self {
+        Self { x, y }
+    }
+    pub fn scale(self, factor: f64) -> Self {
+        Self::new(self.x * factor, self.y * factor)
+    }
+    pub fn length(self) -> f64 {
+        self.x.hypot(self.y)
+    }
+    /// the vector with a length of 1, or the zero vector for itself
+    pub fn normalize(self) -> Self {
+        match self.length() {
+            0. => self,
+            length => self.scale(1. / length),
+        }
+    }
+    /// the z of the cross product of both vectors extended into 3d
+    pub fn cross(self, other: Self) -> f64 {
+        self.x * other.y - self.y * other.x
+    }
+}
+impl Add for Vector {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Self::new(self.x + other.x, self.y + other.y)
+    }
+}
+impl Sub for Vector {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.x - other.x, self.y - other.y)
+    }
+}
+/// a rect with its top left corner at `(x, y)`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+impl Rect {
+    /// whether the rects share some area, which rects that only touch don't
+    pub fn overlaps(&self, other: &Rect) -> bool {
+        self.x < other.x + other.width
+            && other.x < self.x + self.width
+            && self.y < other.y + other.height
+            && other.y < self.y + self.height
+    }
+    /// whether the point is inside, counting the top and left edges but not the bottom and right
+    pub fn contains(&self, point: Vector) -> bool {
+        point.x >= self.x
+            && point.x < self.x + self.width
+            && point.y >= self.y
+            && point.y < self.y + self.height
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Circle {
+    pub center: Vector,
+    pub radius: f64,
+}
+impl Circle {
+    pub fn overlaps(&self, other: &Circle) -> bool {
+        let distance = (self.center - other.center).length();
+        distance < self.radius + other.radius
+    }
+}
+/// the point where the segments from `a` to `b` and from `c` to `d` cross. parallel segments never do
+pub fn segment_intersection((a, b): (Vector, Vector), (c, d): (Vector, Vector)) -> Option<Vector> {
+    let r = b - a;
+    let s = d - c;
+    let denominator = r.cross(s);
+    if denominator == 0. {
+        return None;
+    }
+    let t = (c - a).cross(s) / denominator;
+    let u = (c - a).cross(r) / denominator;
+    ((0. ..=1.).contains(&t) && (0. ..=1.).contains(&u)).then(|| a + r.scale(t))
+}
+/// where a ray first enters a rect
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hit {
+    /// the position of the rect in the list given to `raycast`
+    pub index: usize,
+    pub distance: f64,
+    pub point: Vector,
+    /// points out of the side that was hit, or is zero if the ray starts inside the rect
+    pub normal: Vector,
+}
+/// the closest rect the ray from `origin` along `direction` enters within `max_distance`
+pub fn raycast(
+    origin: Vector,
+    direction: Vector,
+    rects: &[Rect],
+    max_distance: f64,
+) -> Option<Hit> {
+    let direction = direction.normalize();
+    if direction == Vector::default() {
+        return None;
+    }
+    let mut closest: Option<Hit> = None;
+    for (index, rect) in rects.iter().enumerate() {
+        // the distances at which the ray crosses the lines of both sides along one axis
+        let slab = |origin: f64, direction: f64, start: f64, size: f64| {
+            if direction == 0. {
+                if origin >= start && origin <= start + size {
+                    (f64::NEG_INFINITY, f64::INFINITY)
+                } else {
+                    (f64::INFINITY, f64::NEG_INFINITY)
+                }
+            } else {
+                let near = (start - origin) / direction;
+                let far = (start + size - origin) / direction;
+                (near.min(far), near.max(far))
+            }
+        };
+        let (x_near, x_far) = slab(origin.x, direction.x, rect.x, rect.width);
+        let (y_near, y_far) = slab(origin.y, direction.y, rect.y, rect.height);
+        let near = x_near.max(y_near);
+        let far = x_far.min(y_far);
+        if near > far || far < 0. {
+            continue;
+        }
+        let (distance, normal) = if near <= 0. {
+            (0., Vector::default())
+        } else if x_near > y_near {
+            (near, Vector::new(-direction.x.signum(), 0.))
+        } else {
+            (near, Vector::new(0., -direction.y.signum()))
+        };
+        if distance > max_distance || closest.is_some_and(|hit| hit.distance <= distance) {
+            continue;
+        }
+        closest = Some(Hit {
+            index,
+            distance,
+            point: origin + direction.scale(distance),
+            normal,
+        });
+    }
+    closest
+}
+
+/// a 2d vector, made by `geom.vec(x, y)`. its methods return new vectors
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VectorObject(pub Vector);
+impl UserObject for VectorObject {
+    fn typ(&self) -> &'static str {
+        "vec"
+    }
+    fn get(&self, key: &str) -> Option<Value> {
+        match key {
+            "x" => Some(Value::Float(self.0.x)),
+            "y" => Some(Value::Float(self.0.y)),
+            "add" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_add,
+            )))),
+            "sub" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_sub,
+            )))),
+            "scale" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_scale,
+            )))),
+            "length" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_length,
+            )))),
+            "normalize" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_normalize,
+            )))),
+            _ => None,
+        }
+    }
+    fn set(&mut self, key: &str, value: Value) -> Result<(), UserObjectError> {
+        let value = match value {
+            Value::Int(v) => v as f64,
+            Value::Float(v) => v,
+            _ => return Err(UserObjectError::InvalidField(key.into())),
+        };
+        match key {
+            "x" => self.0.x = value,
+            "y" => self.0.y = value,
+            _ => return Err(UserObjectError::InvalidField(key.into())),
+        }
+        Ok(())
+    }
+    fn call(&self, key: &str, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let given = args.clone();
+        match key {
+            "add" => self.call_add(args),
+            "sub" => self.call_sub(args),
+            "scale" => self.call_scale(args),
+            "length" => self.call_length(),
+            "normalize" => self.call_normalize(),
+            _ => Err(UserObjectError::CannotCallNull.into()),
+        }
+        .map_err(|err| method_error(self.typ(), key, Self::params(key), &given, err))
+    }
+}
+impl VectorObject {
+    pub fn params(key: &str) -> &'static [&'static str] {
+        match key {
+            "add" => &["other"],
+            "sub" => &["other"],
+            "scale" => &["factor"],
+            _ => &[],
+        }
+    }
+    pub fn _add(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let _self = _self.borrow();
+            _self.call("add", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    pub fn call_add(&self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let mut args = args.into_iter().enumerate();
+        let other = vector_arg(&mut args)?;
+
+        Ok(vector_value(self.0 + other))
+    }
+    pub fn _sub(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let _self = _self.borrow();
+            _self.call("sub", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    pub fn call_sub(&self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let mut args = args.into_iter().enumerate();
+        let other = vector_arg(&mut args)?;
+
+        Ok(vector_value(self.0 - other))
+    }
+    pub fn _scale(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let _self = _self.borrow();
+            _self.call("scale", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    pub fn call_scale(&self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let mut args = args.into_iter().enumerate();
+        let factor = option!(args:
+            Int => int { int as f64 },
+            Float => float { float }
+        );
+
+        Ok(vector_value(self.0.scale(factor)))
+    }
+    pub fn _length(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let _self = _self.borrow();
+            _self.call("length", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    pub fn call_length(&self) -> Result<Value, Box<dyn Error>> {
+        Ok(Value::Float(self.0.length()))
+    }
+    pub fn _normalize(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let _self = _self.borrow();
+            _self.call("normalize", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    pub fn call_normalize(&self) -> Result<Value, Box<dyn Error>> {
+        Ok(vector_value(self.0.normalize()))
+    }
+}
+pub fn vector_value(vector: Vector) -> Value {
+    Value::UserObject(Rc::new(RefCell::new(Box::new(VectorObject(vector)))))
+}
+
+fn number(value: Option<Value>) -> Option<f64> {
+    match value {
+        Some(Value::Int(v)) => Some(v as f64),
+        Some(Value::Float(v)) => Some(v),
+        _ => None,
+    }
+}
+/// reads the number fields of a vec, or of any object that has them
+fn fields<const N: usize>(
+    value: &Value,
+    shape: &'static str,
+    names: [&'static str; N],
+) -> Result<Option<[f64; N]>, GeomError> {
+    let get: &dyn Fn(&str) -> Option<Value> = match value {
+        Value::Object(object) => &|name| object.borrow().get(name),
+        Value::UserObject(object) if object.borrow().typ() == "vec" => {
+            &|name| object.borrow().get(name)
+        }
+        _ => return Ok(None),
+    };
+    let mut fields = [0.; N];
+    for (field, name) in fields.iter_mut().zip(names) {
+        *field = number(get(name)).ok_or(GeomError::MissingField { shape, field: name })?;
+    }
+    Ok(Some(fields))
+}
+/// a vec or an object with `x` and `y`
+pub fn to_vector(idx: usize, value: &Value) -> Result<Vector, Box<dyn Error>> {
+    let [x, y] = fields(value, "vector", ["x", "y"])?.ok_or_else(|| ExpectedTypes {
+        idx,
+        expected: vec!["vec", "object"],
+        got: value.typ(),
+    })?;
+    Ok(Vector::new(x, y))
+}
+/// an object with `x`, `y`, `width` and `height`
+pub fn to_rect(idx: usize, value: &Value) -> Result<Rect, Box<dyn Error>> {
+    let [x, y, width, height] =
+        fields(value, "rect", ["x", "y", "width", "height"])?.ok_or(ExpectedType {
+            idx,
+            expected: "object",
+            got: value.typ(),
+        })?;
+    Ok(Rect {
+        x,
+        y,
+        width,
+        height,
+    })
+}
+/// an object with `x`, `y` and `radius`
+pub fn to_circle(idx: usize, value: &Value) -> Result<Circle, Box<dyn Error>> {
+    let [x, y, radius] = fields(value, "circle", ["x", "y", "radius"])?.ok_or(ExpectedType {
+        idx,
+        expected: "object",
+        got: value.typ(),
+    })?;
+    Ok(Circle {
+        center: Vector::new(x, y),
+        radius,
+    })
+}
+fn vector_arg<I: Iterator<Item = (usize, Value)>>(args: &mut I) -> Result<Vector, Box<dyn Error>> {
+    let (idx, value) = args.next().unwrap_or_default();
+    to_vector(idx, &value)
+}
+
+/// the `geom` global
+pub fn geom_module() -> Value {
+    object! {
+        "vec" = Value::Function(FunctionKind::UserFunction(Rc::new(|_, args| {
+            let mut args = args.into_iter().enumerate();
+            let x = option!(args:
+                Int => int { int as f64 },
+                Float => float { float }
+            );
+            let y = option!(args:
+                Int => int { int as f64 },
+                Float => float { float }
+            );
+            Ok(vector_value(Vector::new(x, y)))
+        }))),
+        "rects_overlap" = Value::Function(FunctionKind::UserFunction(Rc::new(|_, args| {
+            let mut args = args.into_iter().enumerate();
+            let (idx, a) = args.next().unwrap_or_default();
+            let a = to_rect(idx, &a)?;
+            let (idx, b) = args.next().unwrap_or_default();
+            let b = to_rect(idx, &b)?;
+            Ok(Value::Bool(a.overlaps(&b)))
+        }))),
+        "circles_overlap" = Value::Function(FunctionKind::UserFunction(Rc::new(|_, args| {
+            let mut args = args.into_iter().enumerate();
+            let (idx, a) = args.next().unwrap_or_default();
+            let a = to_circle(idx, &a)?;
+            let (idx, b) = args.next().unwrap_or_default();
+            let b = to_circle(idx, &b)?;
+            Ok(Value::Bool(a.overlaps(&b)))
+        }))),
+        "point_in_rect" = Value::Function(FunctionKind::UserFunction(Rc::new(|_, args| {
+            let mut args = args.into_iter().enumerate();
+            let point = vector_arg(&mut args)?;
+            let (idx, rect) = args.next().unwrap_or_default();
+            let rect = to_rect(idx, &rect)?;
+            Ok(Value::Bool(rect.contains(point)))
+        }))),
+        "segments_intersect" = Value::Function(FunctionKind::UserFunction(Rc::new(|_, args| {
+            let mut args = args.into_iter().enumerate();
+            let a = (vector_arg(&mut args)?, vector_arg(&mut args)?);
+            let b = (vector_arg(&mut args)?, vector_arg(&mut args)?);
+            Ok(segment_intersection(a, b).map(vector_value).unwrap_or_default())
+        }))),
+        "raycast" = Value::Function(FunctionKind::UserFunction(Rc::new(|_, args| {
+            let mut args = args.into_iter().enumerate();
+            let origin = vector_arg(&mut args)?;
+            let direction = vector_arg(&mut args)?;
+            let rects = typed!(args: Vector)
+                .borrow()
+                .iter()
+                .enumerate()
+                .map(|(idx, rect)| match fields(rect, "rect", ["x", "y", "width", "height"]) {
+                    Ok(Some([x, y, width, height])) => Ok(Rect {
+                        x,
+                        y,
+                        width,
+                        height,
+                    }),
+                    Ok(None) => Err(format!("expected object for rects[{idx}], got {}", rect.typ())),
+                    Err(err) => Err(format!("rects[{idx}]: {err}")),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let max_distance = match args.next() {
+                Some((_, Value::Int(distance))) => distance as f64,
+                Some((_, Value::Float(distance))) => distance,
+                None | Some((_, Value::Null)) => f64::INFINITY,
+                Some((idx, arg)) => {
+                    return Err(ExpectedTypes {
+                        idx,
+                        expected: vec!["int", "float"],
+                        got: arg.typ(),
+                    }
+                    .into())
+                }
+            };
+            Ok(match raycast(origin, direction, &rects, max_distance) {
+                Some(hit) => object! {
+                    "index" = hit.index as i64,
+                    "distance" = hit.distance,
+                    "point" = vector_value(hit.point),
+                    "normal" = vector_value(hit.normal)
+                },
+                None => Value::default(),
+            })
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: f64, y: f64, width: f64, height: f64) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn intersects_crossing_segments() {
+        let point = segment_intersection(
+            (Vector::new(0., 0.), Vector::new(4., 4.)),
+            (Vector::new(0., 4.), Vector::new(4., 0.)),
+        );
+        assert_eq!(point, Some(Vector::new(2., 2.)));
+        // segments sharing an end point still touch
+        let point = segment_intersection(
+            (Vector::new(0., 0.), Vector::new(2., 0.)),
+            (Vector::new(2., 0.), Vector::new(2., 3.)),
+        );
+        assert_eq!(point, Some(Vector::new(2., 0.)));
+    }
+
+    #[test]
+    fn misses_apart_and_parallel_segments() {
+        let apart = segment_intersection(
+            (Vector::new(0., 0.), Vector::new(1., 1.)),
+            (Vector::new(3., 0.), Vector::new(2., 1.)),
+        );
+        assert_eq!(apart, None);
+        let parallel = segment_intersection(
+            (Vector::new(0., 0.), Vector::new(4., 0.)),
+            (Vector::new(0., 1.), Vector::new(4., 1.)),
+        );
+        assert_eq!(parallel, None);
+        let collinear = segment_intersection(
+            (Vector::new(0., 0.), Vector::new(4., 0.)),
+            (Vector::new(2., 0.), Vector::new(6., 0.)),
+        );
+        assert_eq!(collinear, None);
+    }
+
+    #[test]
+    fn raycast_hits_the_closest_rect() {
+        let rects = [rect(10., -1., 2., 2.), rect(5., -1., 2., 2.), rect(0., 5., 2., 2.)];
+        let hit = raycast(Vector::default(), Vector::new(3., 0.), &rects, 100.).unwrap();
+        assert_eq!(hit.index, 1);
+        assert_eq!(hit.distance, 5.);
+        assert_eq!(hit.point, Vector::new(5., 0.));
+        assert_eq!(hit.normal, Vector::new(-1., 0.));
+        let hit = raycast(Vector::new(1., 20.), Vector::new(0., -1.), &rects, 100.).unwrap();
+        assert_eq!(hit.index, 2);
+        assert_eq!(hit.distance, 13.);
+        assert_eq!(hit.normal, Vector::new(0., 1.));
+    }
+
+    #[test]
+    fn raycast_misses() {
+        let rects = [rect(5., -1., 2., 2.)];
+        // too short, pointing away, off to the side and without a direction
+        assert_eq!(raycast(Vector::default(), Vector::new(1., 0.), &rects, 4.), None);
+        assert_eq!(raycast(Vector::default(), Vector::new(-1., 0.), &rects, 100.), None);
+        assert_eq!(raycast(Vector::new(0., 3.), Vector::new(1., 0.), &rects, 100.), None);
+        assert_eq!(raycast(Vector::default(), Vector::default(), &rects, 100.), None);
+    }
+
+    #[test]
+    fn raycast_starting_inside() {
+        let rects = [rect(-1., -1., 2., 2.)];
+        let hit = raycast(Vector::default(), Vector::new(1., 1.), &rects, 100.).unwrap();
+        assert_eq!(hit.distance, 0.);
+        assert_eq!(hit.point, Vector::default());
+        assert_eq!(hit.normal, Vector::default());
+    }
+
+    #[test]
+    fn overlaps_ignore_touching_shapes() {
+        assert!(rect(0., 0., 2., 2.).overlaps(&rect(1., 1., 2., 2.)));
+        assert!(!rect(0., 0., 2., 2.).overlaps(&rect(2., 0., 2., 2.)));
+        let circle = |x, radius| Circle {
+            center: Vector::new(x, 0.),
+            radius,
+        };
+        assert!(circle(0., 1.).overlaps(&circle(1.5, 1.)));
+        assert!(!circle(0., 1.).overlaps(&circle(2., 1.)));
+    }
+}
@@ -0,0 +1,179 @@
+use crate::{image::Image, interpreter, require::Source, translation::Options};
+use luna_rs::{
+    compile_str,
+    lang::value::{FunctionKind, Value},
+    luna_impl::position::Located,
+    set_field,
+};
+use std::{
+    cell::{Cell, RefCell},
+    error::Error,
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestConfig {
+    /// the amount of presented frames after which a script is stopped
+    pub frames: u64,
+    /// the largest difference per color channel still counted as a match
+    pub tolerance: u8,
+    /// overwrite the reference images instead of comparing against them
+    pub update: bool,
+}
+impl Default for TestConfig {
+    fn default() -> Self {
+        Self {
+            frames: 60,
+            tolerance: 0,
+            update: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TestError {
+    Exited,
+    NoFrame,
+    MissingReference(PathBuf),
+    SizeMismatch {
+        reference: PathBuf,
+        expected: (u32, u32),
+        got: (u32, u32),
+    },
+    PixelMismatch {
+        reference: PathBuf,
+        pixels: usize,
+    },
+}
+impl Display for TestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TestError::Exited => write!(f, "exited"),
+            TestError::NoFrame => write!(f, "no frame was presented"),
+            TestError::MissingReference(reference) => write!(
+                f,
+                "missing reference {} (run with --update to create it)",
+                reference.display()
+            ),
+            TestError::SizeMismatch {
+                reference,
+                expected: (expected_width, expected_height),
+                got: (width, height),
+            } => write!(
+                f,
+                "expected a {expected_width}x{expected_height} frame like {}, got {width}x{height}",
+                reference.display()
+            ),
+            TestError::PixelMismatch { reference, pixels } => {
+                write!(f, "{pixels} pixels differ from {}", reference.display())
+            }
+        }
+    }
+}
+impl Error for TestError {}
+
+/// runs every `*.luna` file in `dir` and compares its frames against the reference pngs next to it:
+/// `<name>.png` for the last presented frame and `<name>.<frame>.png` for any single frame.
+/// returns whether every script passed
+pub fn run_tests(dir: &Path, config: &TestConfig) -> Result<bool, Box<dyn Error>> {
+    let mut scripts = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "luna") {
+            scripts.push(path);
+        }
+    }
+    scripts.sort();
+    let mut failed = 0;
+    for script in scripts.iter() {
+        match test_script(script, config) {
+            Ok(()) => println!("ok   {}", script.display()),
+            Err(err) => {
+                failed += 1;
+                println!("FAIL {}: {err}", script.display());
+            }
+        }
+    }
+    println!("{} passed, {failed} failed", scripts.len() - failed);
+    Ok(failed == 0)
+}
+
+pub fn test_script(path: &Path, config: &TestConfig) -> Result<(), Box<dyn Error>> {
+    let text = fs::read_to_string(path)?;
+    let closure = compile_str(&text).map_err(|Located { value: err, pos }| {
+        format!("{}:{}: {err}", pos.ln.start + 1, pos.col.start + 1)
+    })?;
+    let options = Options::default();
+    options.frames.capture.set(true);
+    let source = Source::file(&path.display().to_string(), text.clone());
+    options.modules.register(&closure, Rc::new(source));
+    let mut interpreter = interpreter(closure, &options);
+
+    // `exit` would end the whole test run, so it only stops this script
+    let exited = Rc::new(Cell::new(false));
+    {
+        let exited = Rc::clone(&exited);
+        let mut globals = interpreter.globals.borrow_mut();
+        set_field!(globals."exit" = Value::Function(FunctionKind::UserFunction(Rc::new(
+            move |_, _| {
+                exited.set(true);
+                Err(Box::new(TestError::Exited))
+            }
+        ))));
+    }
+
+    let mut errors = vec![];
+    let mut frame = 0;
+    while !interpreter.call_frames.is_empty() && frame < config.frames {
+        if let Err(Located { value: err, pos }) = interpreter.step() {
+            if exited.get() {
+                break;
+            }
+            return Err(format!("{}:{}: {err}", pos.ln.start + 1, pos.col.start + 1).into());
+        }
+        if options.frames.count.get() != frame {
+            frame = options.frames.count.get();
+            let reference = path.with_extension(format!("{frame}.png"));
+            if reference.exists() {
+                if let Some(image) = options.frames.last.borrow().as_ref() {
+                    if let Err(err) = compare(image, reference, config) {
+                        errors.push(err.to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    let last = options.frames.last.borrow_mut().take().ok_or(TestError::NoFrame)?;
+    if let Err(err) = compare(&last, path.with_extension("png"), config) {
+        errors.push(err.to_string());
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("; ").into())
+    }
+}
+
+fn compare(image: &Image, reference: PathBuf, config: &TestConfig) -> Result<(), Box<dyn Error>> {
+    if config.update {
+        return image.save_png(reference);
+    }
+    if !reference.exists() {
+        return Err(TestError::MissingReference(reference).into());
+    }
+    let expected = Image::load_png(&reference)?;
+    match image.difference(&expected, config.tolerance) {
+        Some(0) => Ok(()),
+        Some(pixels) => Err(TestError::PixelMismatch { reference, pixels }.into()),
+        None => Err(TestError::SizeMismatch {
+            reference,
+            expected: (expected.width, expected.height),
+            got: (image.width, image.height),
+        }
+        .into()),
+    }
+}
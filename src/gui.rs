@@ -0,0 +1,415 @@
+use crate::{
+    font::{ADVANCE, GLYPH_HEIGHT},
+    translation::Frames,
+};
+use luna_rs::{
+    lang::value::{FunctionKind, Object, UserObject, Value},
+    luna_impl::interpreter::Interpreter,
+    object, option, typed, ExpectedType, ExpectedTypes,
+};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    error::Error,
+    rc::Rc,
+};
+
+/// the space between the border of a widget and its text
+const PADDING: i32 = 4;
+/// the height of every widget
+const HEIGHT: i32 = GLYPH_HEIGHT + PADDING * 2;
+const FIELD_WIDTH: i32 = 120;
+/// how far from the origin widgets may be placed and how wide they may be, which keeps every sum
+/// of their bounds far from overflowing
+const LIMIT: i32 = 1 << 24;
+
+const BACKGROUND: (u8, u8, u8) = (48, 48, 48);
+const HOVERED: (u8, u8, u8) = (72, 72, 72);
+const ACTIVE: (u8, u8, u8) = (96, 96, 96);
+const ACCENT: (u8, u8, u8) = (80, 140, 220);
+const FOREGROUND: (u8, u8, u8) = (220, 220, 220);
+
+/// the mouse and keyboard as seen through the events the script pulled, which the widgets of the
+/// `gui` module react to. everything that happened once is only kept for the frame it happened in
+#[derive(Debug, Default)]
+pub struct Input {
+    /// the frame the events below were pulled in
+    frame: Cell<u64>,
+    mouse: Cell<(i32, i32)>,
+    /// whether the left button is held
+    down: Cell<bool>,
+    pressed: Cell<bool>,
+    released: Cell<bool>,
+    text: RefCell<String>,
+    keys: RefCell<Vec<String>>,
+    /// the widget the left button went down on
+    active: RefCell<Option<String>>,
+    /// the text field that gets typed text
+    focus: RefCell<Option<String>>,
+}
+impl Input {
+    /// forgets what happened in earlier frames
+    fn sync(&self, frame: u64) {
+        if self.frame.replace(frame) == frame {
+            return;
+        }
+        self.pressed.set(false);
+        self.released.set(false);
+        self.text.borrow_mut().clear();
+        self.keys.borrow_mut().clear();
+        if !self.down.get() {
+            *self.active.borrow_mut() = None;
+        }
+    }
+    /// takes in an event pulled in `frame`, as returned by `events:pull`
+    pub fn update(&self, frame: u64, event: &Value) {
+        self.sync(frame);
+        let Value::Object(event) = event else {
+            return;
+        };
+        let event = event.borrow();
+        let int = |key| match event.get(key) {
+            Some(Value::Int(v)) => v.clamp(i32::MIN.into(), i32::MAX.into()) as i32,
+            _ => 0,
+        };
+        let kind = event.get("kind").unwrap_or_default().to_string();
+        match kind.as_str() {
+            "mouse_motion" => self.mouse.set((int("x"), int("y"))),
+            // only the left button
+            "mouse_button_down" if int("mouse_btn") == 1 => {
+                self.mouse.set((int("x"), int("y")));
+                self.down.set(true);
+                self.pressed.set(true);
+            }
+            "mouse_button_up" if int("mouse_btn") == 1 => {
+                self.mouse.set((int("x"), int("y")));
+                self.down.set(false);
+                self.released.set(true);
+            }
+            "text_input" => self
+                .text
+                .borrow_mut()
+                .push_str(&event.get("text").unwrap_or_default().to_string()),
+            "key_down" => {
+                if let Some(Value::String(keycode)) = event.get("keycode") {
+                    self.keys.borrow_mut().push(keycode);
+                }
+            }
+            _ => {}
+        }
+    }
+    fn hovers(&self, (x, y, width, height): (i32, i32, i32, i32)) -> bool {
+        let (mx, my) = self.mouse.get();
+        mx >= x && mx < x + width && my >= y && my < y + height
+    }
+    fn is_active(&self, id: &str) -> bool {
+        self.active.borrow().as_deref() == Some(id)
+    }
+    /// makes `id` the active widget if the left button went down on it
+    fn press(&self, id: &str, bounds: (i32, i32, i32, i32)) {
+        if self.pressed.get() && self.hovers(bounds) {
+            *self.active.borrow_mut() = Some(id.to_string());
+        }
+    }
+    /// whether the left button went down and up again on the widget
+    fn clicked(&self, id: &str, bounds: (i32, i32, i32, i32)) -> bool {
+        self.press(id, bounds);
+        self.released.get() && self.is_active(id) && self.hovers(bounds)
+    }
+    fn shade(&self, id: &str, bounds: (i32, i32, i32, i32)) -> (u8, u8, u8) {
+        if self.is_active(id) && self.down.get() {
+            ACTIVE
+        } else if self.hovers(bounds) {
+            HOVERED
+        } else {
+            BACKGROUND
+        }
+    }
+}
+
+/// draws through the methods of a `canvas` object. the mouse is tested against what a widget would
+/// cover without a transform, and the draw color is left at the last color a widget used
+struct Painter<'a>(&'a mut dyn UserObject);
+impl Painter<'_> {
+    fn color(&mut self, (r, g, b): (u8, u8, u8)) -> Result<(), Box<dyn Error>> {
+        self.0.call_mut(
+            "color",
+            vec![
+                Value::Int(r.into()),
+                Value::Int(g.into()),
+                Value::Int(b.into()),
+            ],
+        )?;
+        Ok(())
+    }
+    fn rect(
+        &mut self,
+        (x, y, width, height): (i32, i32, i32, i32),
+        fill: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        self.0.call_mut(
+            "rect",
+            vec![
+                Value::Int(x.into()),
+                Value::Int(y.into()),
+                Value::Int(width.into()),
+                Value::Int(height.into()),
+                Value::Bool(fill),
+            ],
+        )?;
+        Ok(())
+    }
+    fn text(&mut self, text: &str, x: i32, y: i32) -> Result<Value, Box<dyn Error>> {
+        self.0.call_mut(
+            "text",
+            vec![text.into(), Value::Int(x.into()), Value::Int(y.into())],
+        )
+    }
+    /// a filled box with a border
+    fn frame(
+        &mut self,
+        bounds: (i32, i32, i32, i32),
+        shade: (u8, u8, u8),
+    ) -> Result<(), Box<dyn Error>> {
+        self.color(shade)?;
+        self.rect(bounds, true)?;
+        self.color(FOREGROUND)?;
+        self.rect(bounds, false)
+    }
+}
+
+fn text_width(text: &str) -> i32 {
+    (text.chars().count().min(LIMIT as usize) as i32 * ADVANCE - 1).max(0)
+}
+/// widgets are told apart by their label and where they are
+fn widget_id(label: &str, x: i32, y: i32) -> String {
+    format!("{label}@{x},{y}")
+}
+
+/// a widget function, given the input and a painter for the canvas that's the first argument
+type Widget = fn(
+    &Input,
+    &mut Painter,
+    &mut dyn ExactSizeIterator<Item = (usize, Value)>,
+) -> Result<Value, Box<dyn Error>>;
+
+/// the `gui` global of immediate mode widgets, drawn into a canvas every frame and reacting to the
+/// events pulled since the last `canvas:present`
+pub fn gui_module(input: &Rc<Input>, frames: &Rc<Frames>) -> Value {
+    let widget = |f: Widget| {
+        let input = Rc::clone(input);
+        let frames = Rc::clone(frames);
+        Value::Function(FunctionKind::UserFunction(Rc::new(
+            move |_: &mut Interpreter, args: Vec<Value>| {
+                let mut args = args.into_iter().enumerate();
+                let canvas = match args.next() {
+                    Some((_, Value::UserObject(canvas))) if canvas.borrow().typ() == "canvas" => {
+                        canvas
+                    }
+                    Some((idx, arg)) => {
+                        return Err(ExpectedType {
+                            idx,
+                            expected: "canvas",
+                            got: arg.typ(),
+                        }
+                        .into())
+                    }
+                    None => {
+                        return Err(ExpectedType {
+                            idx: 0,
+                            expected: "canvas",
+                            got: "null",
+                        }
+                        .into())
+                    }
+                };
+                input.sync(frames.count.get());
+                let mut canvas = canvas.borrow_mut();
+                f(&input, &mut Painter(canvas.as_mut()), &mut args)
+            },
+        )))
+    };
+    object! {
+        "label" = widget(_label),
+        "button" = widget(_button),
+        "checkbox" = widget(_checkbox),
+        "slider" = widget(_slider),
+        "text_field" = widget(_text_field)
+    }
+}
+
+fn position(
+    args: &mut dyn ExactSizeIterator<Item = (usize, Value)>,
+) -> Result<(i32, i32), Box<dyn Error>> {
+    let x = option!(args:
+        Int => int { int.clamp((-LIMIT).into(), LIMIT.into()) as i32 },
+        Float => float { (float as i32).clamp(-LIMIT, LIMIT) }
+    );
+    let y = option!(args:
+        Int => int { int.clamp((-LIMIT).into(), LIMIT.into()) as i32 },
+        Float => float { (float as i32).clamp(-LIMIT, LIMIT) }
+    );
+    Ok((x, y))
+}
+/// `gui.label(canvas, text, x, y)`, returning the y below the text
+fn _label(
+    _: &Input,
+    painter: &mut Painter,
+    args: &mut dyn ExactSizeIterator<Item = (usize, Value)>,
+) -> Result<Value, Box<dyn Error>> {
+    let text = typed!(args: String);
+    let (x, y) = position(args)?;
+
+    painter.color(FOREGROUND)?;
+    painter.text(&text, x, y)
+}
+/// `gui.button(canvas, label, x, y)`, returning whether it was clicked
+fn _button(
+    input: &Input,
+    painter: &mut Painter,
+    args: &mut dyn ExactSizeIterator<Item = (usize, Value)>,
+) -> Result<Value, Box<dyn Error>> {
+    let label = typed!(args: String);
+    let (x, y) = position(args)?;
+
+    let id = widget_id(&label, x, y);
+    let bounds = (x, y, text_width(&label) + PADDING * 2, HEIGHT);
+    let clicked = input.clicked(&id, bounds);
+    painter.frame(bounds, input.shade(&id, bounds))?;
+    painter.text(&label, x + PADDING, y + PADDING)?;
+    Ok(Value::Bool(clicked))
+}
+/// `gui.checkbox(canvas, label, x, y, checked)`, returning whether it's checked now
+fn _checkbox(
+    input: &Input,
+    painter: &mut Painter,
+    args: &mut dyn ExactSizeIterator<Item = (usize, Value)>,
+) -> Result<Value, Box<dyn Error>> {
+    let label = typed!(args: String);
+    let (x, y) = position(args)?;
+    let mut checked = typed!(args: Bool?).unwrap_or_default();
+
+    let id = widget_id(&label, x, y);
+    // the label can be clicked too
+    let bounds = (x, y, HEIGHT + PADDING + text_width(&label), HEIGHT);
+    if input.clicked(&id, bounds) {
+        checked = !checked;
+    }
+    let square = (x, y, HEIGHT, HEIGHT);
+    painter.frame(square, input.shade(&id, bounds))?;
+    if checked {
+        painter.color(ACCENT)?;
+        painter.rect((x + 3, y + 3, HEIGHT - 6, HEIGHT - 6), true)?;
+        painter.color(FOREGROUND)?;
+    }
+    painter.text(&label, x + HEIGHT + PADDING, y + PADDING)?;
+    Ok(Value::Bool(checked))
+}
+/// `gui.slider(canvas, label, x, y, value, min, max, [width])`, returning the value it's set to
+fn _slider(
+    input: &Input,
+    painter: &mut Painter,
+    args: &mut dyn ExactSizeIterator<Item = (usize, Value)>,
+) -> Result<Value, Box<dyn Error>> {
+    let label = typed!(args: String);
+    let (x, y) = position(args)?;
+    let value = option!(args:
+        Int => int { int as f64 },
+        Float => float { float }
+    );
+    let min = option!(args:
+        Int => int { int as f64 },
+        Float => float { float }
+    );
+    let max = option!(args:
+        Int => int { int as f64 },
+        Float => float { float }
+    );
+    let width = typed!(args: Int? int => int.clamp(HEIGHT.into(), LIMIT.into()) as i32)
+        .unwrap_or(FIELD_WIDTH);
+
+    // `clamp` panics on nan bounds
+    for (name, number) in [("value", value), ("min", min), ("max", max)] {
+        if !number.is_finite() {
+            return Err(format!("the {name} of a slider has to be finite, got {number}").into());
+        }
+    }
+    let id = widget_id(&label, x, y);
+    let bounds = (x, y, width, HEIGHT);
+    input.press(&id, bounds);
+    let mut value = value.clamp(min.min(max), max.max(min));
+    if input.is_active(&id) && (input.down.get() || input.released.get()) {
+        let (mx, _) = input.mouse.get();
+        let t = f64::from(mx.saturating_sub(x)) / f64::from(width - 1).max(1.);
+        value = min + (max - min) * t.clamp(0., 1.);
+    }
+    painter.frame(bounds, input.shade(&id, bounds))?;
+    let t = if max == min {
+        0.
+    } else {
+        (value - min) / (max - min)
+    };
+    let knob_x = x + ((f64::from(width - HEIGHT)) * t).round() as i32;
+    painter.color(ACCENT)?;
+    painter.rect((knob_x, y, HEIGHT, HEIGHT), true)?;
+    painter.color(FOREGROUND)?;
+    painter.text(
+        &format!("{label}: {value:.2}"),
+        x + width + PADDING,
+        y + PADDING,
+    )?;
+    Ok(Value::Float(value))
+}
+/// `gui.text_field(canvas, label, x, y, text, [width])`, returning the text as edited so far.
+/// clicking the field focuses it, until enter, escape or a click somewhere else
+fn _text_field(
+    input: &Input,
+    painter: &mut Painter,
+    args: &mut dyn ExactSizeIterator<Item = (usize, Value)>,
+) -> Result<Value, Box<dyn Error>> {
+    let label = typed!(args: String);
+    let (x, y) = position(args)?;
+    let mut text = typed!(args: String?).unwrap_or_default();
+    let width = typed!(args: Int? int => int.clamp(HEIGHT.into(), LIMIT.into()) as i32)
+        .unwrap_or(FIELD_WIDTH);
+
+    let id = widget_id(&label, x, y);
+    let bounds = (x, y, width, HEIGHT);
+    if input.pressed.get() {
+        let mut focus = input.focus.borrow_mut();
+        if input.hovers(bounds) {
+            *focus = Some(id.clone());
+        } else if focus.as_deref() == Some(&id) {
+            *focus = None;
+        }
+    }
+    let focused = input.focus.borrow().as_deref() == Some(&id);
+    if focused {
+        text.push_str(&input.text.borrow());
+        for key in input.keys.borrow().iter() {
+            match key.as_str() {
+                "backspace" => {
+                    text.pop();
+                }
+                "return" | "escape" => *input.focus.borrow_mut() = None,
+                _ => {}
+            }
+        }
+    }
+    let shade = if focused {
+        ACTIVE
+    } else {
+        input.shade(&id, bounds)
+    };
+    painter.frame(bounds, shade)?;
+    // the end of the text stays visible, next to the cursor
+    let fits = ((width - PADDING * 2) / ADVANCE).max(1) as usize - usize::from(focused);
+    let shown = text
+        .chars()
+        .skip(text.chars().count().saturating_sub(fits))
+        .collect::<String>();
+    let cursor = if focused { "_" } else { "" };
+    painter.text(&format!("{shown}{cursor}"), x + PADDING, y + PADDING)?;
+    painter.text(&label, x + width + PADDING, y + PADDING)?;
+    Ok(Value::String(text))
+}
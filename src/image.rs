@@ -0,0 +1,123 @@
+use png::{BitDepth, ColorType, Decoder, Encoder, Transformations};
+use sdl2::{pixels::PixelFormatEnum, surface::Surface};
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+/// rgba pixels with 4 bytes per pixel, row by row
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+impl Image {
+    pub fn load_png<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let mut decoder = Decoder::new(BufReader::new(File::open(path)?));
+        decoder.set_transformations(Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size().ok_or("png is too large")?];
+        let info = reader.next_frame(&mut buffer)?;
+        buffer.truncate(info.buffer_size());
+        let pixels = match info.color_type {
+            ColorType::Rgba => buffer,
+            ColorType::Rgb => buffer
+                .chunks_exact(3)
+                .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+                .collect(),
+            ColorType::GrayscaleAlpha => buffer
+                .chunks_exact(2)
+                .flat_map(|ga| [ga[0], ga[0], ga[0], ga[1]])
+                .collect(),
+            ColorType::Grayscale => buffer.into_iter().flat_map(|g| [g, g, g, 255]).collect(),
+            ColorType::Indexed => return Err("unexpanded indexed png".into()),
+        };
+        Ok(Self {
+            width: info.width,
+            height: info.height,
+            pixels,
+        })
+    }
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn Error>> {
+        let mut encoder = Encoder::new(BufWriter::new(File::create(path)?), self.width, self.height);
+        encoder.set_color(ColorType::Rgba);
+        encoder.set_depth(BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()?;
+        Ok(())
+    }
+    /// writes a png if the path ends in `.png` and a bmp otherwise
+    pub fn save<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Box<dyn Error>> {
+        let path = path.as_ref();
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png")) {
+            return self.save_png(path);
+        }
+        let surface = Surface::from_data(
+            &mut self.pixels,
+            self.width,
+            self.height,
+            self.width * 4,
+            PixelFormatEnum::RGBA32,
+        )?;
+        surface.save_bmp(path)?;
+        Ok(())
+    }
+    /// counts the pixels where any channel differs by more than `tolerance`
+    pub fn difference(&self, other: &Self, tolerance: u8) -> Option<usize> {
+        if (self.width, self.height) != (other.width, other.height) {
+            return None;
+        }
+        Some(
+            self.pixels
+                .chunks_exact(4)
+                .zip(other.pixels.chunks_exact(4))
+                .filter(|(a, b)| a.iter().zip(b.iter()).any(|(a, b)| a.abs_diff(*b) > tolerance))
+                .count(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use std::fs;
+
+    fn image() -> Image {
+        Image {
+            width: 2,
+            height: 1,
+            pixels: vec![255, 0, 0, 255, 0, 0, 255, 128],
+        }
+    }
+
+    #[test]
+    fn saves_png_by_extension() {
+        let dir = TempDir::new("capture");
+        for name in ["shot.png", "SHOT.PNG"] {
+            let path = dir.path().join(name);
+            image().save(&path).unwrap();
+            assert_eq!(fs::read(&path).unwrap()[..8], *b"\x89PNG\r\n\x1a\n");
+            assert_eq!(Image::load_png(&path).unwrap(), image());
+        }
+    }
+
+    #[test]
+    fn difference_counts_pixels_past_the_tolerance() {
+        let mut other = image();
+        other.pixels[0] = 250;
+        other.pixels[7] = 100;
+        assert_eq!(image().difference(&other, 0), Some(2));
+        assert_eq!(image().difference(&other, 5), Some(1));
+        assert_eq!(image().difference(&other, 28), Some(0));
+        let smaller = Image {
+            width: 1,
+            ..image()
+        };
+        assert_eq!(image().difference(&smaller, 255), None);
+    }
+}
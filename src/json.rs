@@ -0,0 +1,217 @@
+use luna_rs::{
+    lang::value::{FunctionKind, Object, Value},
+    object, typed, ExpectedType,
+};
+use serde_json::{Map, Number};
+use std::{cell::RefCell, collections::HashMap, error::Error, fmt::Display, rc::Rc};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonError {
+    Unsupported(&'static str),
+    NonFiniteFloat(f64),
+    /// a vector or object that contains itself
+    Cycle(&'static str),
+}
+impl Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonError::Unsupported(typ) => write!(f, "cannot encode {typ} as json"),
+            JsonError::NonFiniteFloat(v) => write!(f, "cannot encode float {v} as json"),
+            JsonError::Cycle(typ) => write!(f, "cannot encode {typ} that contains itself as json"),
+        }
+    }
+}
+impl Error for JsonError {}
+
+pub fn to_json(value: &Value) -> Result<serde_json::Value, JsonError> {
+    encode(value, &mut vec![])
+}
+/// `parents` holds the vectors and objects `value` is nested in, to catch cycles
+fn encode(value: &Value, parents: &mut Vec<*const ()>) -> Result<serde_json::Value, JsonError> {
+    let ptr = match value {
+        Value::Vector(vector) => Some(Rc::as_ptr(vector) as *const ()),
+        Value::Object(object) => Some(Rc::as_ptr(object) as *const ()),
+        _ => None,
+    };
+    if let Some(ptr) = ptr {
+        if parents.contains(&ptr) {
+            return Err(JsonError::Cycle(value.typ()));
+        }
+        parents.push(ptr);
+    }
+    let json = match value {
+        Value::Null => serde_json::Value::Null,
+        Value::Int(v) => serde_json::Value::Number((*v).into()),
+        Value::Float(v) => serde_json::Value::Number(
+            Number::from_f64(*v).ok_or(JsonError::NonFiniteFloat(*v))?,
+        ),
+        Value::Bool(v) => serde_json::Value::Bool(*v),
+        Value::Char(v) => serde_json::Value::String(v.to_string()),
+        Value::String(v) => serde_json::Value::String(v.clone()),
+        Value::Vector(vector) => serde_json::Value::Array(
+            vector
+                .borrow()
+                .iter()
+                .map(|value| encode(value, parents))
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        Value::Object(object) => {
+            let mut map = Map::new();
+            for (key, value) in object.borrow().fields.iter() {
+                map.insert(key.clone(), encode(value, parents)?);
+            }
+            serde_json::Value::Object(map)
+        }
+        value => return Err(JsonError::Unsupported(value.typ())),
+    };
+    if ptr.is_some() {
+        parents.pop();
+    }
+    Ok(json)
+}
+pub fn from_json(value: serde_json::Value) -> Value {
+    match value {
+        serde_json::Value::Null => Value::Null,
+        serde_json::Value::Bool(v) => Value::Bool(v),
+        serde_json::Value::Number(v) => {
+            if let Some(v) = v.as_i64() {
+                Value::Int(v)
+            } else {
+                Value::Float(v.as_f64().unwrap_or_default())
+            }
+        }
+        serde_json::Value::String(v) => Value::String(v),
+        serde_json::Value::Array(values) => Value::Vector(Rc::new(RefCell::new(
+            values.into_iter().map(from_json).collect(),
+        ))),
+        serde_json::Value::Object(map) => Value::Object(Rc::new(RefCell::new(Object::new(
+            map.into_iter()
+                .map(|(key, value)| (key, from_json(value)))
+                .collect::<HashMap<String, Value>>(),
+        )))),
+    }
+}
+
+/// the `json` global
+pub fn json_module() -> Value {
+    object! {
+        "encode" = Value::Function(FunctionKind::UserFunction(Rc::new(|_, args| {
+            let mut args = args.into_iter().enumerate();
+            let (_, value) = args.next().unwrap_or_default();
+            let pretty = typed!(args: Bool?).unwrap_or_default();
+            let json = to_json(&value)?;
+            Ok(Value::String(if pretty {
+                serde_json::to_string_pretty(&json)?
+            } else {
+                json.to_string()
+            }))
+        }))),
+        "decode" = Value::Function(FunctionKind::UserFunction(Rc::new(|_, args| {
+            let mut args = args.into_iter().enumerate();
+            let text = typed!(args: String);
+            let json = serde_json::from_str(&text).map_err(|err| format!("invalid json: {err}"))?;
+            Ok(from_json(json))
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn vector(values: Vec<Value>) -> Value {
+        Value::Vector(Rc::new(RefCell::new(values)))
+    }
+
+    #[test]
+    fn encodes_values() {
+        let value = object! {
+            "name" = "deimos",
+            "size" = vector(vec![Value::Int(3), Value::Float(0.5)]),
+            "done" = Value::Bool(false),
+            "char" = Value::Char('x'),
+            "none" = Value::Null
+        };
+        assert_eq!(
+            to_json(&value),
+            Ok(json!({
+                "name": "deimos",
+                "size": [3, 0.5],
+                "done": false,
+                "char": "x",
+                "none": null
+            }))
+        );
+    }
+
+    #[test]
+    fn decodes_values() {
+        let value = from_json(json!({ "list": [1, 2.5, "three", true, null] }));
+        let Value::Object(object) = value else {
+            panic!("expected an object, got {value:?}");
+        };
+        let Some(Value::Vector(list)) = object.borrow().get("list") else {
+            panic!("expected a vector");
+        };
+        // vectors compare by identity, so their items are compared instead
+        assert_eq!(
+            *list.borrow(),
+            [
+                Value::Int(1),
+                Value::Float(2.5),
+                Value::String("three".into()),
+                Value::Bool(true),
+                Value::Null,
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        let json = json!({ "level": { "tiles": [[0, 1], [1, 0]], "name": "start" }, "scale": 1.5 });
+        assert_eq!(to_json(&from_json(json.clone())), Ok(json));
+    }
+
+    #[test]
+    fn rejects_cycles() {
+        let inner = Rc::new(RefCell::new(vec![Value::Int(1)]));
+        inner.borrow_mut().push(Value::Vector(Rc::clone(&inner)));
+        assert_eq!(
+            to_json(&Value::Vector(Rc::clone(&inner))),
+            Err(JsonError::Cycle("vector"))
+        );
+        // break the cycle, so the vector gets dropped
+        inner.borrow_mut().clear();
+
+        let object = object! {};
+        if let Value::Object(fields) = &object {
+            fields.borrow_mut().set("me".into(), object.clone());
+            assert_eq!(to_json(&object), Err(JsonError::Cycle("object")));
+            fields.borrow_mut().fields.clear();
+        }
+    }
+
+    #[test]
+    fn allows_shared_values() {
+        let shared = vector(vec![Value::Int(1)]);
+        let value = vector(vec![shared.clone(), shared]);
+        assert_eq!(to_json(&value), Ok(json!([[1], [1]])));
+    }
+
+    #[test]
+    fn rejects_unsupported_values() {
+        assert_eq!(
+            to_json(&Value::Float(f64::NAN)).map_err(|err| err.to_string()),
+            Err("cannot encode float NaN as json".into())
+        );
+        let Value::Object(module) = json_module() else {
+            panic!("expected an object");
+        };
+        let encode = module.borrow().get("encode").unwrap();
+        assert_eq!(
+            to_json(&vector(vec![encode.clone()])),
+            Err(JsonError::Unsupported(encode.typ()))
+        );
+    }
+}
@@ -1,58 +1,269 @@
-use std::{cell::RefCell, env, fs, process::exit, rc::Rc};
+use std::{cell::RefCell, env, error::Error, fs, io, path::{Path, PathBuf}, process::exit, rc::Rc};
 
 use luna_rs::{
     compile_str,
-    lang::{code::Closure, value::{Function, Value}},
-    luna_impl::{interpreter::{Interpreter, RunTimeError}, position::Located},
+    lang::{code::Closure, value::Function},
+    luna_impl::{interpreter::Interpreter, position::Located},
 };
-use translation::insert_module;
+use bundle::Bundle;
+use cli::{Command, Script};
+use golden::run_tests;
+use manifest::Manifest;
+use repl::repl;
+use overlay::show_error;
+use report::{describe_trace, report};
+use require::Source;
+use watch::watch;
+use translation::{insert_module, Options};
 
+pub mod bundle;
+pub mod cli;
+pub mod color;
+pub mod font;
+pub mod geom;
+pub mod golden;
+pub mod gui;
+pub mod image;
+pub mod json;
+pub mod manifest;
+pub mod overlay;
+pub mod physics;
+pub mod repl;
+pub mod replay;
+pub mod require;
+pub mod report;
+pub mod sandbox;
+pub mod sprite;
+pub mod storage;
+#[cfg(test)]
+mod test_util;
+pub mod tiled;
+pub mod tilemap;
 pub mod translation;
+pub mod watch;
 
 fn main() {
-    let mut args = env::args().skip(1);
-    if let Some(path) = args.next() {
-        let text = fs::read_to_string(&path)
+    // a bundled game runs itself, passing every argument on to the script
+    let command = match Bundle::embedded() {
+        Ok(Some(bundle)) => bundle.command().unwrap_or_else(|err| {
+            eprintln!("ERROR: {err}");
+            exit(1);
+        }),
+        Ok(None) => match cli::parse(env::args().skip(1)) {
+            Ok(command) => command,
+            Err(err) => {
+                eprintln!("ERROR: {err}\n{}", USAGE);
+                exit(1);
+            }
+        },
+        Err(err) => {
+            eprintln!("ERROR: {err}");
+            exit(1);
+        }
+    };
+    let mut command = match command {
+        Command::Run {
+            script: Script::Project(dir),
+            headless,
+            watch,
+            overlay,
+            options,
+        } => match project(&dir, options) {
+            Ok((path, options)) => Command::Run {
+                script: Script::File(path),
+                headless,
+                watch,
+                overlay,
+                options,
+            },
+            Err(err) => {
+                eprintln!("ERROR {dir}: {err}");
+                exit(1);
+            }
+        },
+        command => command,
+    };
+    // a script keeps its save data whether it's watched or not
+    if let Command::Run {
+        script: Script::File(path),
+        options,
+        ..
+    } = &mut command
+    {
+        if options.app.is_none() {
+            options.app = Path::new(path)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned());
+        }
+    }
+    match command {
+        Command::Help => println!("{}", USAGE),
+        Command::Version => println!("deimos {}", env!("CARGO_PKG_VERSION")),
+        Command::Repl { headless, options } => {
+            if headless {
+                set_headless();
+            }
+            if let Err(err) = repl(&options) {
+                eprintln!("ERROR: {err}");
+                exit(1);
+            }
+        }
+        Command::Bundle {
+            input,
+            output,
+            assets,
+        } => {
+            let assets = assets.iter().map(PathBuf::from).collect::<Vec<_>>();
+            let bundle = Bundle::collect(Path::new(&input), &assets)
+                .and_then(|bundle| bundle.write(Path::new(&output)).map(|_| bundle));
+            match bundle {
+                Ok(bundle) => println!("bundled {} files into {output}", bundle.files.len()),
+                Err(err) => {
+                    eprintln!("ERROR {input}: {err}");
+                    exit(1);
+                }
+            }
+        }
+        Command::Test { dir, config } => {
+            set_headless();
+            match run_tests(Path::new(&dir), &config) {
+                Ok(true) => {}
+                Ok(false) => exit(1),
+                Err(err) => {
+                    eprintln!("ERROR {dir}: {err}");
+                    exit(1);
+                }
+            }
+        }
+        Command::Run {
+            script: Script::File(path),
+            headless,
+            watch: true,
+            options,
+            ..
+        } => {
+            if headless {
+                set_headless();
+            }
+            if let Err(err) = watch(&path, &options) {
+                eprintln!("ERROR {path}: {err}");
+                exit(1);
+            }
+        }
+        Command::Run {
+            script,
+            headless,
+            overlay,
+            mut options,
+            ..
+        } => {
+            if headless {
+                set_headless();
+            }
+            // headless or replayed runs never get the key press that closes the overlay
+            if overlay && !headless && options.replay.is_none() {
+                // keeps track of the windows, so the error can be shown in one of them
+                options.reuse = Some(Rc::default());
+            }
+            let name = script.name();
+            let text = match &script {
+                Script::File(path) | Script::Project(path) => fs::read_to_string(path),
+                Script::Stdin => io::read_to_string(io::stdin()),
+                Script::Inline(code) => Ok(code.clone()),
+            }
             .map_err(|err| {
                 eprintln!("ERROR: {err}");
                 exit(1);
             })
             .unwrap();
-        let closure = compile_str(&text)
-            .map_err(|Located { value: err, pos }| {
-                eprintln!(
-                    "ERROR {path}:{}:{}: {err}",
-                    pos.ln.start + 1,
-                    pos.col.start + 1
-                );
+            let closure = compile_str(&text)
+                .map_err(|Located { value: err, pos }| {
+                    report(name, &text, err, &pos);
+                    exit(1);
+                })
+                .unwrap();
+            let source = match &script {
+                Script::File(path) => Source::file(path, text.clone()),
+                _ => Source {
+                    name: name.to_string(),
+                    dir: None,
+                    text: text.clone(),
+                },
+            };
+            options.modules.register(&closure, Rc::new(source));
+            let mut interpreter = interpreter(closure, &options);
+            if let Err(Located { value: err, pos }) = interpreter.run() {
+                let description =
+                    describe_trace(name, &text, err, &pos, &interpreter, &options.modules);
+                eprintln!("{description}");
+                if let Some(reuse) = &options.reuse {
+                    if let Err(err) = show_error(reuse, &description) {
+                        eprintln!("ERROR: {err}");
+                    }
+                }
                 exit(1);
-            })
-            .unwrap();
-        run(closure).map_err(|Located { value: err, pos }| {
-            eprintln!(
-                "ERROR {path}:{}:{}: {err}",
-                pos.ln.start + 1,
-                pos.col.start + 1
-            );
-            exit(1);
-        })
-        .unwrap();
-    } else {
-        eprintln!("{}", USAGE);
-        exit(1);
+            }
+        }
     }
 }
+/// reads the manifest of the project in `dir`, returning its entry script and the options it sets
+fn project(dir: &str, options: Options) -> Result<(String, Options), Box<dyn Error>> {
+    let manifest = Manifest::load(Path::new(dir))?;
+    let options = Options {
+        app: options.app.or(Some(manifest.name)),
+        window: manifest.window,
+        search_paths: manifest.search_paths,
+        assets: manifest.assets,
+        ..options
+    };
+    Ok((manifest.entry.display().to_string(), options))
+}
+/// switches sdl to its dummy video driver, so every canvas draws into an offscreen surface
+pub fn set_headless() {
+    env::set_var("SDL_VIDEODRIVER", "dummy");
+    sdl2::hint::set("SDL_RENDER_DRIVER", "software");
+}
 pub const USAGE: &str = r#"USAGE:
-    deimos <input.luna> - runs the luna file
+    deimos [flags] - starts a repl that keeps its globals and windows between lines
+    deimos [flags] <input.luna> [--] [args...] - runs the luna file, passing the args to it
+    deimos [flags] - [--] [args...] - runs the luna code read from stdin
+    deimos [flags] -e <code> [--] [args...] - runs the given luna code
+    deimos run [flags] [dir] [--] [args...] - runs the project described by the deimos.toml
+        in the directory (default the current one)
+    deimos test [test flags] <dir> - runs every luna file in the directory headlessly
+        and compares its frames against the reference pngs next to it
+    deimos bundle <input> [-o <output>] [--assets <dir>]... - writes an executable that runs the
+        script or project directory, embedding it with the modules it requires by literal path,
+        the manifest and asset directory of a project and every directory given to --assets
+
+FLAGS:
+    -h, --help - prints this message
+    -V, --version - prints the version of deimos
+    --error-overlay - shows runtime errors in the window instead of closing it, unless the
+        script runs headless or from a replay
+    --watch - reruns the script whenever its file or a module it required changes, keeping
+        its windows open
+    --headless - runs without a display, drawing every canvas into an offscreen surface
+    --replay <input.json> - feeds the recorded events to `events:pull` instead of the real devices
+    --record <output.json> - records every event pulled through `events:pull`
+    --allow-fs=<dir> - only lets `fs`, `require`, textures, captures and `tiled.load` touch
+        files inside the directory, and takes away `net`, `os.exec` and `env.set_current_dir`.
+        `storage` still saves to the user's data dir and `io` still reads stdin
+
+TEST FLAGS:
+    --frames <n> - stops each script after n presented frames (default 60)
+    --tolerance <n> - the largest difference per color channel still counted as a match (default 0)
+    --update - writes the references instead of comparing against them
 "#;
 
-pub fn run(closure: Rc<RefCell<Closure>>) -> Result<Option<Value>, Located<RunTimeError>> {
+/// sets up an interpreter with the deimos modules that is ready to run `closure`
+pub fn interpreter(closure: Rc<RefCell<Closure>>, options: &Options) -> Interpreter {
     let mut interpreter = Interpreter::default();
-    insert_module(&mut interpreter.globals.borrow_mut());
+    insert_module(&mut interpreter.globals.borrow_mut(), options);
     interpreter.call(&Rc::new(Function {
         closure,
         upvalues: vec![]
     }), vec![], None);
-    interpreter.run()
+    interpreter
 }
 
@@ -0,0 +1,242 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+};
+use toml::{Table, Value};
+
+/// the file `deimos run` looks for in the project directory
+pub const MANIFEST: &str = "deimos.toml";
+
+/// the settings of a project, read from its `deimos.toml`:
+/// ```toml
+/// name = "my-game"
+/// entry = "main.luna"
+/// assets = "assets"
+/// search_paths = ["lib"]
+///
+/// [window]
+/// title = "my game"
+/// width = 640
+/// height = 480
+/// ```
+/// every path is relative to the directory of the manifest
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifest {
+    /// names the save data of the game, defaults to the name of the directory
+    pub name: String,
+    pub entry: PathBuf,
+    pub window: WindowDefaults,
+    /// directories `require` looks in after the directory of the calling script
+    pub search_paths: Vec<PathBuf>,
+    /// the directory images are loaded from
+    pub assets: Option<PathBuf>,
+}
+/// used for the arguments left out of `sdl:canvas`
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowDefaults {
+    pub title: String,
+    pub width: u32,
+    pub height: u32,
+}
+impl Default for WindowDefaults {
+    fn default() -> Self {
+        Self {
+            title: "deimos".to_string(),
+            width: 800,
+            height: 600,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ManifestError {
+    ExpectedType(&'static str, String),
+    InvalidSize(String, i64),
+}
+impl Display for ManifestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ManifestError::ExpectedType(expected, key) => write!(f, "expected {expected} for {key}"),
+            ManifestError::InvalidSize(key, size) => write!(f, "invalid size {size} for {key}"),
+        }
+    }
+}
+impl Error for ManifestError {}
+
+impl Manifest {
+    pub fn load(dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let path = dir.join(MANIFEST);
+        let text = fs::read_to_string(&path)
+            .map_err(|err| format!("couldn't read {}: {err}", path.display()))?;
+        let table: Table = text.parse()?;
+
+        let name = match table.get("name") {
+            Some(name) => string(name, "name")?.to_string(),
+            None => dir_name(dir),
+        };
+        let entry = match table.get("entry") {
+            Some(entry) => dir.join(string(entry, "entry")?),
+            None => dir.join("main.luna"),
+        };
+        let assets = table
+            .get("assets")
+            .map(|assets| string(assets, "assets").map(|assets| dir.join(assets)))
+            .transpose()?;
+        let search_paths = match table.get("search_paths") {
+            Some(Value::Array(paths)) => paths
+                .iter()
+                .map(|path| string(path, "search_paths").map(|path| dir.join(path)))
+                .collect::<Result<_, _>>()?,
+            Some(_) => {
+                return Err(ManifestError::ExpectedType("a list", "search_paths".into()).into())
+            }
+            None => vec![],
+        };
+        let mut window = WindowDefaults::default();
+        match table.get("window") {
+            Some(Value::Table(table)) => {
+                if let Some(title) = table.get("title") {
+                    window.title = string(title, "window.title")?.to_string();
+                }
+                if let Some(width) = table.get("width") {
+                    window.width = size(width, "window.width")?;
+                }
+                if let Some(height) = table.get("height") {
+                    window.height = size(height, "window.height")?;
+                }
+            }
+            Some(_) => return Err(ManifestError::ExpectedType("a table", "window".into()).into()),
+            None => {}
+        }
+        Ok(Self {
+            name,
+            entry,
+            window,
+            search_paths,
+            assets,
+        })
+    }
+}
+
+/// the name of `dir`, even if it's given as `.`
+pub fn dir_name(dir: &Path) -> String {
+    dir.canonicalize()
+        .ok()
+        .and_then(|dir| Some(dir.file_name()?.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| "game".to_string())
+}
+fn string<'a>(value: &'a Value, key: &str) -> Result<&'a str, ManifestError> {
+    value
+        .as_str()
+        .ok_or_else(|| ManifestError::ExpectedType("a string", key.to_string()))
+}
+fn size(value: &Value, key: &str) -> Result<u32, ManifestError> {
+    let size = value
+        .as_integer()
+        .ok_or_else(|| ManifestError::ExpectedType("an integer", key.to_string()))?;
+    match u32::try_from(size) {
+        Ok(size) if size > 0 => Ok(size),
+        _ => Err(ManifestError::InvalidSize(key.to_string(), size)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    fn load(dir: &TempDir, manifest: &str) -> Result<Manifest, String> {
+        dir.write(MANIFEST, manifest);
+        Manifest::load(dir.path()).map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn defaults() {
+        let dir = TempDir::new("manifest-defaults");
+        let root = dir.path();
+        assert_eq!(
+            load(&dir, ""),
+            Ok(Manifest {
+                name: dir_name(root),
+                entry: root.join("main.luna"),
+                window: WindowDefaults::default(),
+                search_paths: vec![],
+                assets: None,
+            })
+        );
+        assert!(dir_name(root).ends_with("manifest-defaults"));
+    }
+
+    #[test]
+    fn reads_every_setting() {
+        let dir = TempDir::new("manifest-settings");
+        let root = dir.path();
+        let manifest = load(
+            &dir,
+            r#"
+name = "my-game"
+entry = "src/game.luna"
+assets = "assets"
+search_paths = ["lib", "vendor"]
+
+[window]
+title = "my game"
+width = 640
+"#,
+        );
+        assert_eq!(
+            manifest,
+            Ok(Manifest {
+                name: "my-game".into(),
+                entry: root.join("src/game.luna"),
+                window: WindowDefaults {
+                    title: "my game".into(),
+                    width: 640,
+                    height: 600,
+                },
+                search_paths: vec![root.join("lib"), root.join("vendor")],
+                assets: Some(root.join("assets")),
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_settings() {
+        let dir = TempDir::new("manifest-errors");
+        let error = |manifest| load(&dir, manifest).unwrap_err();
+        assert_eq!(
+            error("name = 1"),
+            ManifestError::ExpectedType("a string", "name".into()).to_string()
+        );
+        assert_eq!(
+            error("search_paths = \"lib\""),
+            ManifestError::ExpectedType("a list", "search_paths".into()).to_string()
+        );
+        assert_eq!(
+            error("search_paths = [\"lib\", 2]"),
+            ManifestError::ExpectedType("a string", "search_paths".into()).to_string()
+        );
+        assert_eq!(
+            error("window = 1"),
+            ManifestError::ExpectedType("a table", "window".into()).to_string()
+        );
+        assert_eq!(
+            error("[window]\nwidth = 0"),
+            ManifestError::InvalidSize("window.width".into(), 0).to_string()
+        );
+        assert_eq!(
+            error("[window]\nheight = 4294967296"),
+            ManifestError::InvalidSize("window.height".into(), 4294967296).to_string()
+        );
+        assert!(error("name = ").contains("TOML parse error"));
+    }
+
+    #[test]
+    fn needs_a_manifest() {
+        let dir = TempDir::new("manifest-missing");
+        let err = Manifest::load(dir.path()).unwrap_err().to_string();
+        assert!(err.starts_with("couldn't read"), "{err}");
+    }
+}
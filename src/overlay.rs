@@ -0,0 +1,61 @@
+use crate::translation::Reuse;
+use luna_rs::lang::value::{UserObject, Value};
+use std::{error::Error, thread, time::Duration};
+
+/// draws `description` over the first canvas the script opened, until a key is pressed or the
+/// window is closed. returns whether there was a canvas to draw on
+pub fn show_error(reuse: &Reuse, description: &str) -> Result<bool, Box<dyn Error>> {
+    let Some((_, mut canvas)) = reuse.canvases.borrow().first().cloned() else {
+        return Ok(false);
+    };
+    canvas.reset();
+    let events = reuse.events.borrow().clone();
+    let events = match events {
+        Some(events) => events,
+        None => {
+            let Some(Value::UserObject(sdl)) = reuse.sdl.borrow().clone() else {
+                return Ok(false);
+            };
+            let events = sdl.borrow().call("events", vec![])?;
+            events
+        }
+    };
+    let Value::UserObject(events) = events else {
+        return Ok(false);
+    };
+    loop {
+        draw(&mut canvas, description)?;
+        loop {
+            let Value::Object(event) = events.borrow_mut().call_mut("pull", vec![])? else {
+                break;
+            };
+            let event = event.borrow();
+            let kind = event.get("kind").unwrap_or_default().to_string();
+            let win_event = event.get("win_event").unwrap_or_default().to_string();
+            if kind == "quit" || kind == "key_down" || win_event == "close" {
+                return Ok(true);
+            }
+        }
+        thread::sleep(Duration::from_millis(16));
+    }
+}
+
+fn draw(canvas: &mut dyn UserObject, description: &str) -> Result<(), Box<dyn Error>> {
+    canvas.call_mut("scale", vec![Value::Float(1.), Value::Float(1.)])?;
+    canvas.call_mut("color", vec![Value::Int(48), Value::Int(8), Value::Int(8)])?;
+    canvas.call_mut("clear", vec![])?;
+    canvas.call_mut("color", vec![Value::Int(255), Value::Int(96), Value::Int(96)])?;
+    let y = canvas.call_mut(
+        "text",
+        vec!["the script stopped".into(), Value::Int(8), Value::Int(8), Value::Int(2)],
+    )?;
+    canvas.call_mut("color", vec![Value::Int(255), Value::Int(255), Value::Int(255)])?;
+    let y = canvas.call_mut("text", vec![description.into(), Value::Int(8), y])?;
+    canvas.call_mut("color", vec![Value::Int(160), Value::Int(160), Value::Int(160)])?;
+    canvas.call_mut(
+        "text",
+        vec!["\npress any key to close".into(), Value::Int(8), y],
+    )?;
+    canvas.call_mut("present", vec![])?;
+    Ok(())
+}
@@ -0,0 +1,614 @@
+use crate::{
+    geom::{to_rect, to_vector, vector_value, Rect, Vector},
+    translation::method_error,
+};
+use luna_rs::{
+    lang::value::{FunctionKind, Object, UserObject, UserObjectError, Value},
+    luna_impl::interpreter::Interpreter,
+    object, option, typed, ExpectedType, ExpectedTypes,
+};
+use std::{cell::RefCell, collections::HashMap, error::Error, rc::Rc};
+
+/// the most steps `world:step` splits one call into, so bodies moving at the same time meet where
+/// they would have. a single move is swept, so even the last substep can't pass through a body
+const MAX_SUBSTEPS: u32 = 16;
+const STATIC_COLOR: (i64, i64, i64) = (128, 128, 128);
+const DYNAMIC_COLOR: (i64, i64, i64) = (96, 220, 96);
+
+/// an axis aligned box. dynamic bodies fall and get pushed out of other bodies, static ones never move
+#[derive(Debug, Clone, PartialEq)]
+pub struct Body {
+    pub id: u64,
+    pub rect: Rect,
+    pub velocity: Vector,
+    pub dynamic: bool,
+    /// whether the body landed on another during the last step
+    pub on_ground: bool,
+}
+/// two bodies that touched during a step, where `normal` points the way `a` was pushed out of `b`
+#[derive(Debug, Clone)]
+pub struct Collision {
+    pub a: Rc<RefCell<Body>>,
+    pub b: Rc<RefCell<Body>>,
+    pub normal: Vector,
+}
+
+/// a set of bodies moved by `world:step(dt)`, made by `physics.world([gravity])`
+#[derive(Debug, Default)]
+pub struct WorldObject {
+    gravity: Vector,
+    bodies: Vec<Rc<RefCell<Body>>>,
+    next_id: u64,
+    /// what collided during the last step
+    collisions: Vec<Collision>,
+}
+impl UserObject for WorldObject {
+    fn typ(&self) -> &'static str {
+        "world"
+    }
+    fn get(&self, key: &str) -> Option<Value> {
+        match key {
+            "gravity" => Some(vector_value(self.gravity)),
+            "collisions" => Some(
+                self.collisions
+                    .iter()
+                    .map(|collision| {
+                        object! {
+                            "a" = body_value(&collision.a),
+                            "b" = body_value(&collision.b),
+                            "normal" = vector_value(collision.normal)
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .into(),
+            ),
+            "bodies" => Some(
+                self.bodies
+                    .iter()
+                    .map(body_value)
+                    .collect::<Vec<_>>()
+                    .into(),
+            ),
+            "add_body" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_add_body,
+            )))),
+            "remove_body" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_remove_body,
+            )))),
+            "step" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_step,
+            )))),
+            "draw" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_draw,
+            )))),
+            _ => None,
+        }
+    }
+    fn set(&mut self, key: &str, value: Value) -> Result<(), UserObjectError> {
+        match key {
+            "gravity" => {
+                self.gravity =
+                    gravity(0, value).map_err(|_| UserObjectError::InvalidField(key.into()))?;
+                Ok(())
+            }
+            _ => Err(UserObjectError::InvalidField(key.into())),
+        }
+    }
+    fn call(&self, key: &str, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let given = args.clone();
+        match key {
+            "draw" => self.call_draw(args),
+            _ => Err(UserObjectError::CannotCallNull.into()),
+        }
+        .map_err(|err| method_error(self.typ(), key, Self::params(key), &given, err))
+    }
+    fn call_mut(&mut self, key: &str, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let given = args.clone();
+        match key {
+            "add_body" => self.call_add_body(args),
+            "remove_body" => self.call_remove_body(args),
+            "step" => self.call_step(args),
+            _ => return self.call(key, args),
+        }
+        .map_err(|err| method_error(self.typ(), key, Self::params(key), &given, err))
+    }
+}
+impl WorldObject {
+    pub fn params(key: &str) -> &'static [&'static str] {
+        match key {
+            "add_body" => &["rect", "dynamic"],
+            "remove_body" => &["body"],
+            "step" => &["dt"],
+            "draw" => &["canvas"],
+            _ => &[],
+        }
+    }
+    pub fn _add_body(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let mut _self = _self.borrow_mut();
+            _self.call_mut("add_body", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    /// adds a body covering `rect`, which is dynamic unless told otherwise
+    pub fn call_add_body(&mut self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let mut args = args.into_iter().enumerate();
+        let (idx, rect) = args.next().unwrap_or_default();
+        let rect = to_rect(idx, &rect)?;
+        let dynamic = typed!(args: Bool?).unwrap_or(true);
+
+        let body = Rc::new(RefCell::new(Body {
+            id: self.next_id,
+            rect,
+            velocity: Vector::default(),
+            dynamic,
+            on_ground: false,
+        }));
+        self.next_id += 1;
+        self.bodies.push(Rc::clone(&body));
+        Ok(body_value(&body))
+    }
+    pub fn _remove_body(
+        _: &mut Interpreter,
+        mut args: Vec<Value>,
+    ) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let mut _self = _self.borrow_mut();
+            _self.call_mut("remove_body", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    pub fn call_remove_body(&mut self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let mut args = args.into_iter().enumerate();
+        let (idx, body) = args.next().unwrap_or_default();
+        let id = body_id(&body).ok_or(ExpectedType {
+            idx,
+            expected: "body",
+            got: body.typ(),
+        })?;
+
+        self.bodies.retain(|body| body.borrow().id != id);
+        Ok(Value::default())
+    }
+    pub fn _step(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let mut _self = _self.borrow_mut();
+            _self.call_mut("step", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    /// moves the world `dt` seconds ahead and replaces `collisions` with what touched meanwhile
+    pub fn call_step(&mut self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let mut args = args.into_iter().enumerate();
+        let dt = option!(args:
+            Int => int { int as f64 },
+            Float => float { float }
+        );
+
+        self.step(dt.max(0.));
+        Ok(Value::default())
+    }
+    pub fn step(&mut self, dt: f64) {
+        self.collisions.clear();
+        for body in &self.bodies {
+            body.borrow_mut().on_ground = false;
+        }
+        // no body may move more than half of the smallest body in one substep
+        let smallest = self
+            .bodies
+            .iter()
+            .map(|body| body.borrow().rect.width.min(body.borrow().rect.height))
+            .filter(|size| *size > 0.)
+            .fold(f64::INFINITY, f64::min);
+        let fastest = self
+            .bodies
+            .iter()
+            .filter(|body| body.borrow().dynamic)
+            .map(|body| (body.borrow().velocity + self.gravity.scale(dt)).length() * dt)
+            .fold(0., f64::max);
+        let substeps = (fastest / (smallest / 2.)).ceil();
+        let substeps = if substeps.is_finite() {
+            (substeps as u32).clamp(1, MAX_SUBSTEPS)
+        } else {
+            1
+        };
+        let dt = dt / f64::from(substeps);
+        for _ in 0..substeps {
+            for body in self.bodies.iter().filter(|body| body.borrow().dynamic) {
+                let velocity = {
+                    let mut body = body.borrow_mut();
+                    body.velocity = body.velocity + self.gravity.scale(dt);
+                    body.velocity
+                };
+                advance(
+                    &self.bodies,
+                    &mut self.collisions,
+                    body,
+                    Vector::new(velocity.x * dt, 0.),
+                );
+                advance(
+                    &self.bodies,
+                    &mut self.collisions,
+                    body,
+                    Vector::new(0., velocity.y * dt),
+                );
+            }
+        }
+    }
+    pub fn _draw(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let _self = _self.borrow();
+            _self.call("draw", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    /// outlines every body on `canvas`, static ones in gray and dynamic ones in green. the draw
+    /// color is left at the color of the last body
+    pub fn call_draw(&self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let mut args = args.into_iter().enumerate();
+        let canvas = match args.next() {
+            Some((_, Value::UserObject(canvas))) if canvas.borrow().typ() == "canvas" => canvas,
+            Some((idx, arg)) => {
+                return Err(ExpectedType {
+                    idx,
+                    expected: "canvas",
+                    got: arg.typ(),
+                }
+                .into())
+            }
+            None => {
+                return Err(ExpectedType {
+                    idx: 0,
+                    expected: "canvas",
+                    got: "null",
+                }
+                .into())
+            }
+        };
+
+        let mut canvas = canvas.borrow_mut();
+        for body in &self.bodies {
+            let body = body.borrow();
+            let (r, g, b) = if body.dynamic {
+                DYNAMIC_COLOR
+            } else {
+                STATIC_COLOR
+            };
+            canvas.call_mut("color", vec![Value::Int(r), Value::Int(g), Value::Int(b)])?;
+            let rect = body.rect;
+            canvas.call_mut(
+                "rect",
+                vec![
+                    Value::Float(rect.x),
+                    Value::Float(rect.y),
+                    Value::Float(rect.width),
+                    Value::Float(rect.height),
+                ],
+            )?;
+        }
+        Ok(Value::default())
+    }
+}
+
+/// moves `body` by `delta` along one axis and pushes it back out of what it ran into
+fn advance(
+    bodies: &[Rc<RefCell<Body>>],
+    collisions: &mut Vec<Collision>,
+    body: &Rc<RefCell<Body>>,
+    delta: Vector,
+) {
+    // bodies that overlap without moving towards each other are left alone
+    if delta == Vector::default() {
+        return;
+    }
+    let start = body.borrow().rect;
+    // everything the body passes over on the way, so it stops at a body thinner than the move
+    let swept = Rect {
+        x: start.x + delta.x.min(0.),
+        y: start.y + delta.y.min(0.),
+        width: start.width + delta.x.abs(),
+        height: start.height + delta.y.abs(),
+    };
+    // where the body would stop against each other body in the way, along the axis it moves on
+    let stops = bodies
+        .iter()
+        .filter(|other| !Rc::ptr_eq(body, other))
+        .filter_map(|other| {
+            let rect = other.borrow().rect;
+            if !swept.overlaps(&rect) {
+                return None;
+            }
+            let stop = if delta.x > 0. {
+                rect.x - start.width
+            } else if delta.x < 0. {
+                rect.x + rect.width
+            } else if delta.y > 0. {
+                rect.y - start.height
+            } else {
+                rect.y + rect.height
+            };
+            Some((other, stop))
+        })
+        .collect::<Vec<_>>();
+    let (axis_start, axis_delta) = if delta.x != 0. {
+        (start.x, delta.x)
+    } else {
+        (start.y, delta.y)
+    };
+    // the closest stop along the move, or the end of the move if nothing is in the way
+    let end = stops.iter().fold(axis_start + axis_delta, |end, (_, stop)| {
+        if axis_delta > 0. {
+            end.min(*stop)
+        } else {
+            end.max(*stop)
+        }
+    });
+    let normal = if delta.x != 0. {
+        Vector::new(-delta.x.signum(), 0.)
+    } else {
+        Vector::new(0., -delta.y.signum())
+    };
+    {
+        let mut moved = body.borrow_mut();
+        if delta.x != 0. {
+            moved.rect.x = end;
+        } else {
+            moved.rect.y = end;
+        }
+    }
+    for (other, _) in stops.into_iter().filter(|(_, stop)| *stop == end) {
+        {
+            let mut moved = body.borrow_mut();
+            if delta.x != 0. {
+                moved.velocity.x = 0.;
+            } else {
+                moved.velocity.y = 0.;
+                moved.on_ground |= delta.y > 0.;
+            }
+        }
+        let seen = collisions
+            .iter()
+            .any(|collision| Rc::ptr_eq(&collision.a, body) && Rc::ptr_eq(&collision.b, other));
+        if !seen {
+            collisions.push(Collision {
+                a: Rc::clone(body),
+                b: Rc::clone(other),
+                normal,
+            });
+        }
+    }
+}
+
+/// a handle to a body of a world, which reads and writes the body the world moves
+#[derive(Debug, Clone)]
+pub struct BodyObject(Rc<RefCell<Body>>);
+impl UserObject for BodyObject {
+    fn typ(&self) -> &'static str {
+        "body"
+    }
+    fn get(&self, key: &str) -> Option<Value> {
+        let body = self.0.borrow();
+        match key {
+            "id" => Some(Value::Int(body.id as i64)),
+            "x" => Some(Value::Float(body.rect.x)),
+            "y" => Some(Value::Float(body.rect.y)),
+            "width" => Some(Value::Float(body.rect.width)),
+            "height" => Some(Value::Float(body.rect.height)),
+            "vx" => Some(Value::Float(body.velocity.x)),
+            "vy" => Some(Value::Float(body.velocity.y)),
+            "position" => Some(vector_value(Vector::new(body.rect.x, body.rect.y))),
+            "velocity" => Some(vector_value(body.velocity)),
+            "dynamic" => Some(Value::Bool(body.dynamic)),
+            "on_ground" => Some(Value::Bool(body.on_ground)),
+            _ => None,
+        }
+    }
+    fn set(&mut self, key: &str, value: Value) -> Result<(), UserObjectError> {
+        let invalid = || UserObjectError::InvalidField(key.into());
+        let mut body = self.0.borrow_mut();
+        match (key, value) {
+            ("position", value) => {
+                let position = to_vector(0, &value).map_err(|_| invalid())?;
+                body.rect.x = position.x;
+                body.rect.y = position.y;
+            }
+            ("velocity", value) => body.velocity = to_vector(0, &value).map_err(|_| invalid())?,
+            ("dynamic", Value::Bool(dynamic)) => body.dynamic = dynamic,
+            (key, Value::Int(v)) => *number_field(&mut body, key).ok_or_else(invalid)? = v as f64,
+            (key, Value::Float(v)) => *number_field(&mut body, key).ok_or_else(invalid)? = v,
+            _ => return Err(invalid()),
+        }
+        Ok(())
+    }
+}
+fn number_field<'a>(body: &'a mut Body, key: &str) -> Option<&'a mut f64> {
+    match key {
+        "x" => Some(&mut body.rect.x),
+        "y" => Some(&mut body.rect.y),
+        "width" => Some(&mut body.rect.width),
+        "height" => Some(&mut body.rect.height),
+        "vx" => Some(&mut body.velocity.x),
+        "vy" => Some(&mut body.velocity.y),
+        _ => None,
+    }
+}
+fn body_value(body: &Rc<RefCell<Body>>) -> Value {
+    Value::UserObject(Rc::new(RefCell::new(Box::new(BodyObject(Rc::clone(body))))))
+}
+/// the id of the `body` object in `value`
+fn body_id(value: &Value) -> Option<u64> {
+    let Value::UserObject(object) = value else {
+        return None;
+    };
+    let object = object.borrow();
+    if object.typ() != "body" {
+        return None;
+    }
+    match object.get("id") {
+        Some(Value::Int(id)) => id.try_into().ok(),
+        _ => None,
+    }
+}
+/// a vec, or a number for gravity pulling straight down
+fn gravity(idx: usize, value: Value) -> Result<Vector, Box<dyn Error>> {
+    match value {
+        Value::Int(y) => Ok(Vector::new(0., y as f64)),
+        Value::Float(y) => Ok(Vector::new(0., y)),
+        Value::Null => Ok(Vector::default()),
+        value => to_vector(idx, &value).map_err(|_| {
+            ExpectedTypes {
+                idx,
+                expected: vec!["int", "float", "vec", "object"],
+                got: value.typ(),
+            }
+            .into()
+        }),
+    }
+}
+
+/// the `physics` global
+pub fn physics_module() -> Value {
+    object! {
+        "world" = Value::Function(FunctionKind::UserFunction(Rc::new(|_, args| {
+            let mut args = args.into_iter().enumerate();
+            let (idx, value) = args.next().unwrap_or_default();
+            let world = WorldObject {
+                gravity: gravity(idx, value)?,
+                ..Default::default()
+            };
+            Ok(Value::UserObject(Rc::new(RefCell::new(Box::new(world)))))
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add(world: &mut WorldObject, rect: Rect, dynamic: bool) -> Rc<RefCell<Body>> {
+        let body = Rc::new(RefCell::new(Body {
+            id: world.bodies.len() as u64,
+            rect,
+            velocity: Vector::default(),
+            dynamic,
+            on_ground: false,
+        }));
+        world.bodies.push(Rc::clone(&body));
+        body
+    }
+    fn rect(x: f64, y: f64, width: f64, height: f64) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+    fn world_with_floor() -> (WorldObject, Rc<RefCell<Body>>, Rc<RefCell<Body>>) {
+        let mut world = WorldObject {
+            gravity: Vector::new(0., 100.),
+            ..Default::default()
+        };
+        let player = add(&mut world, rect(0., 0., 10., 10.), true);
+        let floor = add(&mut world, rect(-50., 20., 100., 10.), false);
+        (world, player, floor)
+    }
+
+    #[test]
+    fn falls_onto_the_ground() {
+        let (mut world, player, floor) = world_with_floor();
+        for _ in 0..60 {
+            world.step(1. / 60.);
+        }
+        let body = player.borrow();
+        assert_eq!(body.rect.y, 10.);
+        assert_eq!(body.velocity.y, 0.);
+        assert!(body.on_ground);
+        assert_eq!(world.collisions.len(), 1);
+        let collision = &world.collisions[0];
+        assert!(Rc::ptr_eq(&collision.a, &player));
+        assert!(Rc::ptr_eq(&collision.b, &floor));
+        assert_eq!(collision.normal, Vector::new(0., -1.));
+        // static bodies stay where they are
+        assert_eq!(floor.borrow().rect, rect(-50., 20., 100., 10.));
+    }
+
+    #[test]
+    fn forgets_contacts_between_steps() {
+        let (mut world, player, _) = world_with_floor();
+        for _ in 0..60 {
+            world.step(1. / 60.);
+        }
+        player.borrow_mut().velocity = Vector::new(0., -200.);
+        world.step(1. / 60.);
+        assert!(!player.borrow().on_ground);
+        assert!(world.collisions.is_empty());
+    }
+
+    #[test]
+    fn fast_bodies_do_not_tunnel() {
+        let (mut world, player, _) = world_with_floor();
+        player.borrow_mut().velocity = Vector::new(0., 10_000.);
+        world.step(1. / 60.);
+        assert_eq!(player.borrow().rect.y, 10.);
+        assert!(player.borrow().on_ground);
+    }
+
+    #[test]
+    fn walls_stop_sideways_movement() {
+        let mut world = WorldObject::default();
+        let player = add(&mut world, rect(0., 0., 10., 10.), true);
+        let wall = add(&mut world, rect(15., -10., 10., 30.), false);
+        player.borrow_mut().velocity = Vector::new(600., 0.);
+        world.step(1. / 60.);
+        let body = player.borrow();
+        assert_eq!(body.rect.x, 5.);
+        assert_eq!(body.velocity.x, 0.);
+        assert!(!body.on_ground);
+        assert_eq!(world.collisions.len(), 1);
+        assert!(Rc::ptr_eq(&world.collisions[0].b, &wall));
+        assert_eq!(world.collisions[0].normal, Vector::new(-1., 0.));
+    }
+
+    #[test]
+    fn stops_at_floors_thinner_than_a_substep() {
+        let mut world = WorldObject::default();
+        let player = add(&mut world, rect(0., 0., 10., 10.), true);
+        let floor = add(&mut world, rect(-50., 515., 100., 1.), false);
+        // far more than MAX_SUBSTEPS steps of half the floor's height
+        player.borrow_mut().velocity = Vector::new(0., 60_000.);
+        world.step(1. / 60.);
+        let body = player.borrow();
+        assert_eq!(body.rect.y, 505.);
+        assert!(body.on_ground);
+        assert_eq!(world.collisions.len(), 1);
+        assert!(Rc::ptr_eq(&world.collisions[0].b, &floor));
+
+        drop(body);
+        player.borrow_mut().velocity = Vector::new(0., -60_000.);
+        player.borrow_mut().rect.y = 600.;
+        world.step(1. / 60.);
+        assert_eq!(player.borrow().rect.y, 516.);
+        assert_eq!(world.collisions[0].normal, Vector::new(0., 1.));
+    }
+}
@@ -0,0 +1,173 @@
+use crate::{
+    report::{report, report_trace},
+    translation::{insert_module, Options, Reuse},
+    watch::idle,
+};
+use luna_rs::{
+    compile_str,
+    lang::value::{Function, Value},
+    luna_impl::{interpreter::Interpreter, position::Located},
+};
+use std::{
+    error::Error,
+    io::{self, Write},
+    rc::Rc,
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+/// reads luna code line by line and runs it in one interpreter, so globals like a canvas
+/// survive between lines. input is read until its braces and strings are closed, errors are only
+/// reported. while waiting for input the windows keep getting their events, like under `--watch`
+pub fn repl(options: &Options) -> Result<(), Box<dyn Error>> {
+    let reuse = Rc::new(Reuse::default());
+    let options = Options {
+        reuse: Some(Rc::clone(&reuse)),
+        ..options.clone()
+    };
+    let mut interpreter = Interpreter::default();
+    insert_module(&mut interpreter.globals.borrow_mut(), &options);
+    // stdin is read on its own thread, so waiting for a line doesn't freeze the windows
+    let (sender, lines) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lines() {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    let mut input = String::new();
+    loop {
+        print!("{}", if input.is_empty() { "> " } else { "... " });
+        io::stdout().flush()?;
+        let line = loop {
+            match lines.recv_timeout(Duration::from_millis(16)) {
+                Ok(line) => break Some(line?),
+                Err(RecvTimeoutError::Timeout) => idle(&reuse)?,
+                Err(RecvTimeoutError::Disconnected) => break None,
+            }
+        };
+        let Some(line) = line else {
+            println!();
+            return Ok(());
+        };
+        input.push_str(&line);
+        input.push('\n');
+        if unfinished(&input) {
+            continue;
+        }
+        let mut code = std::mem::take(&mut input);
+        if code.trim().is_empty() {
+            continue;
+        }
+        // try a single line as an expression first to print its value
+        if !code.trim().contains('\n') {
+            let expression = format!("return {code}");
+            if compile_str(&expression).is_ok() {
+                code = expression;
+            }
+        }
+        match run_line(&mut interpreter, &code, &options) {
+            Some(Value::Null) | None => {}
+            Some(value) => println!("{value:?}"),
+        }
+    }
+}
+
+/// runs one input of the repl, reporting its errors. whatever a failed line left behind is
+/// dropped, so the next line starts from the globals alone
+fn run_line(interpreter: &mut Interpreter, code: &str, options: &Options) -> Option<Value> {
+    let closure = match compile_str(code) {
+        Ok(closure) => closure,
+        Err(Located { value: err, pos }) => {
+            report("<repl>", code, err, &pos);
+            return None;
+        }
+    };
+    let level = interpreter.call_frames.len();
+    interpreter.call(&Rc::new(Function {
+        closure,
+        upvalues: vec![]
+    }), vec![], None);
+    let result = interpreter.run();
+    let value = match result {
+        Ok(value) => value,
+        Err(Located { value: err, pos }) => {
+            // the trace is read from the frames, so they're only dropped after reporting it
+            report_trace("<repl>", code, err, &pos, interpreter, &options.modules);
+            None
+        }
+    };
+    interpreter.call_frames.truncate(level);
+    value
+}
+
+/// whether `code` leaves a block or string open. braces in strings, chars and comments don't count
+fn unfinished(code: &str) -> bool {
+    let mut depth = 0;
+    let mut chars = code.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            '#' => {
+                chars.by_ref().find(|c| *c == '\n');
+            }
+            '"' | '\'' => loop {
+                match chars.next() {
+                    Some('\\') => {
+                        chars.next();
+                    }
+                    Some(end) if end == c => break,
+                    Some(_) => {}
+                    // an unclosed char is an error rather than more input
+                    None => return c == '"',
+                }
+            },
+            _ => {}
+        }
+    }
+    depth > 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repl() -> (Interpreter, Options) {
+        let options = Options::default();
+        let interpreter = Interpreter::default();
+        insert_module(&mut interpreter.globals.borrow_mut(), &options);
+        (interpreter, options)
+    }
+
+    #[test]
+    fn keeps_globals_between_lines() {
+        let (mut interpreter, options) = repl();
+        assert_eq!(run_line(&mut interpreter, "x = 2", &options), None);
+        assert_eq!(run_line(&mut interpreter, "return x * 3", &options), Some(Value::Int(6)));
+        assert!(interpreter.call_frames.is_empty());
+    }
+
+    #[test]
+    fn recovers_from_failed_lines() {
+        let (mut interpreter, options) = repl();
+        let code = "x = 1\nfn deep(n) {\n    if n == 0 { return 1 + {} }\n    return deep(n - 1)\n}\ndeep(5)";
+        assert_eq!(run_line(&mut interpreter, code, &options), None);
+        assert!(interpreter.call_frames.is_empty());
+        assert_eq!(run_line(&mut interpreter, "return x + 1", &options), Some(Value::Int(2)));
+        assert_eq!(run_line(&mut interpreter, "let = ", &options), None);
+        assert!(interpreter.call_frames.is_empty());
+    }
+
+    #[test]
+    fn waits_for_open_blocks_and_strings() {
+        assert!(unfinished("fn f() {"));
+        assert!(unfinished("let s = \"{"));
+        assert!(!unfinished("let s = \"{\""));
+        assert!(!unfinished("let c = '{'"));
+        assert!(!unfinished("let x = 1 # {"));
+        assert!(!unfinished("if x { }"));
+    }
+}
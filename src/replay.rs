@@ -0,0 +1,166 @@
+use crate::json::{from_json, to_json};
+use luna_rs::lang::value::Value;
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fmt::Display,
+    fs::{self, File},
+    io::{Seek, SeekFrom, Write},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayError {
+    ExpectedEvents,
+    ExpectedEvent(usize),
+    ExpectedFrame(usize),
+}
+impl Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::ExpectedEvents => write!(f, "expected a list of events"),
+            ReplayError::ExpectedEvent(idx) => write!(f, "expected an object for event #{idx}"),
+            ReplayError::ExpectedFrame(idx) => {
+                write!(f, "expected a frame number for event #{idx}")
+            }
+        }
+    }
+}
+impl Error for ReplayError {}
+
+/// recorded events waiting to be pulled, each tagged with the frame it is due on
+pub struct Replay(VecDeque<(u64, Value)>);
+impl Replay {
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let text = fs::read_to_string(path)?;
+        let serde_json::Value::Array(entries) = serde_json::from_str(&text)? else {
+            return Err(ReplayError::ExpectedEvents.into());
+        };
+        let mut events = VecDeque::with_capacity(entries.len());
+        for (idx, entry) in entries.into_iter().enumerate() {
+            let serde_json::Value::Object(mut event) = entry else {
+                return Err(ReplayError::ExpectedEvent(idx).into());
+            };
+            let frame = event
+                .remove("frame")
+                .and_then(|frame| frame.as_u64())
+                .ok_or(ReplayError::ExpectedFrame(idx))?;
+            events.push_back((frame, from_json(serde_json::Value::Object(event))));
+        }
+        Ok(Self(events))
+    }
+    pub fn next(&mut self, frame: u64) -> Option<Value> {
+        if self.0.front()?.0 <= frame {
+            self.0.pop_front().map(|(_, event)| event)
+        } else {
+            None
+        }
+    }
+}
+
+/// appends every pulled event to a json list, keeping the file valid after each write
+/// since a script usually ends through `exit`, which skips any cleanup
+pub struct Recorder {
+    file: File,
+    empty: bool,
+}
+impl Recorder {
+    pub fn create(path: &str) -> Result<Self, Box<dyn Error>> {
+        let mut file = File::create(path)?;
+        file.write_all(b"[]")?;
+        Ok(Self { file, empty: true })
+    }
+    pub fn record(&mut self, frame: u64, event: &Value) -> Result<(), Box<dyn Error>> {
+        let mut entry = to_json(event)?;
+        if let serde_json::Value::Object(entry) = &mut entry {
+            entry.insert("frame".into(), frame.into());
+        }
+        let entry = serde_json::to_string(&entry)?;
+        if self.empty {
+            self.file.seek(SeekFrom::End(-1))?;
+            write!(self.file, "\n    {entry}\n]")?;
+            self.empty = false;
+        } else {
+            self.file.seek(SeekFrom::End(-2))?;
+            write!(self.file, ",\n    {entry}\n]")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use luna_rs::{lang::value::Object, object};
+    use serde_json::json;
+    use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+    fn event(kind: &str, x: i64) -> Value {
+        object! {
+            "kind" = kind,
+            "x" = Value::Int(x)
+        }
+    }
+
+    #[test]
+    fn replays_what_was_recorded() {
+        let dir = TempDir::new("replay");
+        let path = dir.path().join("events.json").display().to_string();
+        let mut recorder = Recorder::create(&path).unwrap();
+        recorder.record(0, &event("mouse_down", 1)).unwrap();
+        recorder.record(2, &event("mouse_up", 2)).unwrap();
+        recorder.record(2, &event("quit", 3)).unwrap();
+        // the file is valid json after every event
+        let text = fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&text).unwrap(),
+            json!([
+                { "frame": 0, "kind": "mouse_down", "x": 1 },
+                { "frame": 2, "kind": "mouse_up", "x": 2 },
+                { "frame": 2, "kind": "quit", "x": 3 }
+            ])
+        );
+
+        let mut replay = Replay::load(&path).unwrap();
+        let mut next = |frame| replay.next(frame).map(|event| to_json(&event).unwrap());
+        assert_eq!(next(0), Some(json!({ "kind": "mouse_down", "x": 1 })));
+        assert_eq!(next(0), None);
+        assert_eq!(next(1), None);
+        assert_eq!(next(2), Some(json!({ "kind": "mouse_up", "x": 2 })));
+        assert_eq!(next(5), Some(json!({ "kind": "quit", "x": 3 })));
+        assert_eq!(next(5), None);
+    }
+
+    #[test]
+    fn empty_recording() {
+        let dir = TempDir::new("empty-replay");
+        let path = dir.path().join("events.json").display().to_string();
+        Recorder::create(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[]");
+        assert_eq!(Replay::load(&path).unwrap().next(u64::MAX), None);
+    }
+
+    #[test]
+    fn rejects_malformed_replays() {
+        let dir = TempDir::new("bad-replay");
+        let error = |text: &str| {
+            let path = dir.path().join("events.json");
+            fs::write(&path, text).unwrap();
+            Replay::load(&path.display().to_string()).err().map(|err| err.to_string())
+        };
+        assert_eq!(error("{}"), Some(ReplayError::ExpectedEvents.to_string()));
+        assert_eq!(
+            error(r#"[{ "frame": 0 }, 1]"#),
+            Some(ReplayError::ExpectedEvent(1).to_string())
+        );
+        assert_eq!(
+            error(r#"[{ "kind": "quit" }]"#),
+            Some(ReplayError::ExpectedFrame(0).to_string())
+        );
+        assert_eq!(
+            error(r#"[{ "frame": -1 }]"#),
+            Some(ReplayError::ExpectedFrame(0).to_string())
+        );
+        assert!(error("[").is_some());
+    }
+}
@@ -0,0 +1,99 @@
+use crate::require::Modules;
+use luna_rs::luna_impl::{
+    interpreter::{CallFrame, Interpreter},
+    position::Position,
+};
+use std::fmt::Display;
+
+/// prints the error with the offending source line and a caret under its columns
+pub fn report<E: Display>(name: &str, text: &str, err: E, pos: &Position) {
+    eprintln!("{}", describe(name, text, err, pos));
+}
+/// like `report`, followed by the luna call stack the error happened in
+pub fn report_trace<E: Display>(
+    name: &str,
+    text: &str,
+    err: E,
+    pos: &Position,
+    interpreter: &Interpreter,
+    modules: &Modules,
+) {
+    eprintln!("{}", describe_trace(name, text, err, pos, interpreter, modules));
+}
+
+pub fn describe<E: Display>(name: &str, text: &str, err: E, pos: &Position) -> String {
+    let mut description = format!(
+        "ERROR {name}:{}:{}: {err}",
+        pos.ln.start + 1,
+        pos.col.start + 1
+    );
+    if let Some(snippet) = snippet(text, pos) {
+        description.push('\n');
+        description.push_str(&snippet);
+    }
+    description
+}
+/// `name` and `text` are used for code that isn't known to `modules`
+pub fn describe_trace<E: Display>(
+    name: &str,
+    text: &str,
+    err: E,
+    pos: &Position,
+    interpreter: &Interpreter,
+    modules: &Modules,
+) -> String {
+    let source = |frame: &CallFrame| modules.source(&frame.function.closure);
+    // the error happened in the innermost frame, which may belong to a required module
+    let innermost = interpreter.call_frames.last().and_then(source);
+    let (error_name, error_text) = innermost
+        .as_ref()
+        .map(|source| (source.name.as_str(), source.text.as_str()))
+        .unwrap_or((name, text));
+    let mut description = describe(error_name, error_text, err, pos);
+    if interpreter.call_frames.len() > 1 {
+        description.push_str("\nstack trace (most recent call first):");
+        for frame in interpreter.call_frames.iter().rev() {
+            let Some(pos) = position(frame) else {
+                continue;
+            };
+            let source = source(frame);
+            let (name, text) = source
+                .as_ref()
+                .map(|source| (source.name.as_str(), source.text.as_str()))
+                .unwrap_or((name, text));
+            let line = text.lines().nth(pos.ln.start).unwrap_or_default().trim();
+            description.push_str(&format!(
+                "\n    {name}:{}:{}: {line}",
+                pos.ln.start + 1,
+                pos.col.start + 1
+            ));
+        }
+    }
+    description
+}
+
+pub fn snippet(text: &str, pos: &Position) -> Option<String> {
+    let line = text.lines().nth(pos.ln.start)?;
+    let number = (pos.ln.start + 1).to_string();
+    let margin = " ".repeat(number.len());
+    // keep tabs in the indentation, so the caret lines up with the source
+    let indent: String = line
+        .chars()
+        .take(pos.col.start)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let end = if pos.ln.end > pos.ln.start + 1 {
+        line.chars().count()
+    } else {
+        pos.col.end
+    };
+    let carets = "^".repeat(end.saturating_sub(pos.col.start).max(1));
+    Some(format!("{margin} |\n{number} | {line}\n{margin} | {indent}{carets}"))
+}
+/// the position the call frame is currently at
+pub fn position(frame: &CallFrame) -> Option<Position> {
+    // `idx` already points past the instruction being executed
+    let closure = frame.function.closure.borrow();
+    let located = closure.code.get(frame.idx.saturating_sub(1))?;
+    Some(located.pos.clone())
+}
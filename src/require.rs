@@ -0,0 +1,233 @@
+use crate::sandbox::allowed;
+use luna_rs::{
+    compile_str,
+    lang::{
+        code::Closure,
+        value::{Function, Value},
+    },
+    luna_impl::{interpreter::Interpreter, position::Located},
+    typed, ExpectedType,
+};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    error::Error,
+    fmt::Display,
+    fs, iter,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+/// a compiled piece of luna code and where it came from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Source {
+    pub name: String,
+    /// the directory `require` resolves paths against, if the code came from a file
+    pub dir: Option<PathBuf>,
+    pub text: String,
+}
+impl Source {
+    pub fn file(path: &str, text: String) -> Self {
+        Self {
+            name: path.to_string(),
+            dir: Path::new(path).parent().map(Path::to_path_buf),
+            text,
+        }
+    }
+}
+
+pub type ClosureSource = (Rc<RefCell<Closure>>, Rc<Source>);
+
+/// the modules loaded through `require` and the source of every closure compiled so far
+#[derive(Debug, Default)]
+pub struct Modules {
+    cache: RefCell<HashMap<PathBuf, Value>>,
+    loading: RefCell<Vec<PathBuf>>,
+    sources: RefCell<Vec<ClosureSource>>,
+    /// every module file read so far, even those that failed to load
+    files: RefCell<Vec<PathBuf>>,
+}
+impl Modules {
+    /// remembers `source` for `closure` and every closure nested in it
+    pub fn register(&self, closure: &Rc<RefCell<Closure>>, source: Rc<Source>) {
+        for child in closure.borrow().closures.iter() {
+            self.register(child, Rc::clone(&source));
+        }
+        self.sources.borrow_mut().push((Rc::clone(closure), source));
+    }
+    pub fn source(&self, closure: &Rc<RefCell<Closure>>) -> Option<Rc<Source>> {
+        self.sources
+            .borrow()
+            .iter()
+            .find(|(other, _)| Rc::ptr_eq(other, closure))
+            .map(|(_, source)| Rc::clone(source))
+    }
+    pub fn files(&self) -> Vec<PathBuf> {
+        self.files.borrow().clone()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RequireError {
+    NotFound(String),
+    Circular(PathBuf),
+}
+impl Display for RequireError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequireError::NotFound(path) => write!(f, "couldn't find module {path:?}"),
+            RequireError::Circular(path) => {
+                write!(f, "module {} requires itself", path.display())
+            }
+        }
+    }
+}
+impl Error for RequireError {}
+
+/// loads `path` relative to the file of the calling code, or else one of `search_paths`, runs it
+/// once and caches its return value.
+/// `path` may leave out the `.luna` extension or point to a directory with a `mod.luna`.
+/// with `--allow-fs` the module has to be inside the allowed directory, wherever it was found
+pub fn _require(
+    interpreter: &mut Interpreter,
+    args: Vec<Value>,
+    modules: &Modules,
+    search_paths: &[PathBuf],
+    root: Option<&Path>,
+) -> Result<Value, Box<dyn Error>> {
+    let mut args = args.into_iter().enumerate();
+    let path = typed!(args: String);
+    let dir = interpreter
+        .call_frames
+        .last()
+        .and_then(|frame| modules.source(&frame.function.closure))
+        .and_then(|source| source.dir.clone())
+        .unwrap_or_default();
+    let file = iter::once(&dir)
+        .chain(search_paths)
+        .find_map(|dir| resolve(dir, &path))
+        .ok_or(RequireError::NotFound(path))?;
+    // read what was checked, so a symlink swapped in afterwards can't lead outside of the root
+    let checked = allowed(root, &file)?;
+    let key = fs::canonicalize(&checked)?;
+    if let Some(value) = modules.cache.borrow().get(&key) {
+        return Ok(value.clone());
+    }
+    if modules.loading.borrow().contains(&key) {
+        return Err(RequireError::Circular(file).into());
+    }
+
+    if !modules.files.borrow().contains(&key) {
+        modules.files.borrow_mut().push(key.clone());
+    }
+    let name = file.display().to_string();
+    let text = fs::read_to_string(&checked)?;
+    let closure = compile_str(&text).map_err(|Located { value: err, pos }| {
+        format!("{name}:{}:{}: {err}", pos.ln.start + 1, pos.col.start + 1)
+    })?;
+    modules.register(&closure, Rc::new(Source::file(&name, text)));
+
+    modules.loading.borrow_mut().push(key.clone());
+    let level = interpreter.call_frames.len();
+    interpreter.call(&Rc::new(Function {
+        closure,
+        upvalues: vec![]
+    }), vec![], None);
+    let result = interpreter.run();
+    modules.loading.borrow_mut().pop();
+    let value = result
+        .map_err(|Located { value: err, pos }| {
+            // the error is reported at the `require`, so the module's frames are done
+            interpreter.call_frames.truncate(level);
+            format!("{name}:{}:{}: {err}", pos.ln.start + 1, pos.col.start + 1)
+        })?
+        .unwrap_or_default();
+    modules.cache.borrow_mut().insert(key, value.clone());
+    Ok(value)
+}
+
+pub fn resolve(dir: &Path, path: &str) -> Option<PathBuf> {
+    let path = dir.join(path);
+    [
+        path.clone(),
+        PathBuf::from(format!("{}.luna", path.display())),
+        path.join("mod.luna"),
+    ]
+    .into_iter()
+    .find(|candidate| candidate.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{interpreter, test_util::TempDir, translation::Options};
+
+    /// runs `main.luna` in `dir` with the deimos globals, returning what it returned
+    fn run(dir: &TempDir, options: Options) -> Result<Value, String> {
+        let path = dir.path().join("main.luna").display().to_string();
+        let text = fs::read_to_string(&path).unwrap();
+        let closure = compile_str(&text).unwrap();
+        options.modules.register(&closure, Rc::new(Source::file(&path, text)));
+        let mut interpreter = interpreter(closure, &options);
+        interpreter
+            .run()
+            .map(Option::unwrap_or_default)
+            .map_err(|err| err.value.to_string())
+    }
+
+    #[test]
+    fn resolves_module_paths() {
+        let dir = TempDir::new("resolve");
+        dir.write("exact", "");
+        dir.write("short.luna", "");
+        dir.write("package/mod.luna", "");
+        let root = dir.path();
+        assert_eq!(resolve(root, "exact"), Some(root.join("exact")));
+        assert_eq!(resolve(root, "short"), Some(root.join("short.luna")));
+        assert_eq!(resolve(root, "short.luna"), Some(root.join("short.luna")));
+        assert_eq!(resolve(root, "package"), Some(root.join("package/mod.luna")));
+        assert_eq!(resolve(root, "missing"), None);
+    }
+
+    #[test]
+    fn runs_each_module_once() {
+        let dir = TempDir::new("require-once");
+        dir.write(
+            "main.luna",
+            "loads = 0\nlet a = require(\"lib/counter\")\nlet b = require(\"./lib/counter.luna\")\nreturn a + b + loads",
+        );
+        dir.write("lib/counter.luna", "loads = loads + 1\nreturn require(\"../lib/value\")");
+        dir.write("lib/value.luna", "return 10");
+        let options = Options::default();
+        assert_eq!(run(&dir, options.clone()), Ok(Value::Int(21)));
+        let files = ["lib/counter.luna", "lib/value.luna"].map(|file| dir.path().join(file));
+        assert_eq!(options.modules.files(), files);
+    }
+
+    #[test]
+    fn finds_modules_in_search_paths() {
+        let dir = TempDir::new("require-search");
+        dir.write("main.luna", "return require(\"shared\")");
+        dir.write("libs/shared.luna", "return 5");
+        assert!(run(&dir, Options::default()).is_err());
+        let options = Options {
+            search_paths: vec![dir.path().join("libs")],
+            ..Default::default()
+        };
+        assert_eq!(run(&dir, options), Ok(Value::Int(5)));
+    }
+
+    #[test]
+    fn reports_missing_and_circular_modules() {
+        let dir = TempDir::new("require-errors");
+        dir.write("main.luna", "return require(\"missing\")");
+        let err = run(&dir, Options::default()).unwrap_err();
+        assert!(err.contains(&RequireError::NotFound("missing".into()).to_string()), "{err}");
+
+        dir.write("main.luna", "return require(\"a\")");
+        dir.write("a.luna", "return require(\"b\")");
+        dir.write("b.luna", "return require(\"a\")");
+        let err = run(&dir, Options::default()).unwrap_err();
+        assert!(err.contains("requires itself"), "{err}");
+    }
+}
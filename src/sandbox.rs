@@ -0,0 +1,250 @@
+use luna_rs::{
+    lang::value::{FunctionKind, Object, Value},
+    luna_impl::{
+        interpreter::Interpreter,
+        std::{_fs_open, _fs_type},
+    },
+    object, typed, ExpectedType,
+};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    env,
+    error::Error,
+    fmt::Display,
+    fs,
+    path::{Component, Path, PathBuf},
+    rc::Rc,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SandboxError {
+    /// the path is outside of the directory given to `--allow-fs`
+    Denied(PathBuf),
+    MissingRoot(PathBuf),
+}
+impl Display for SandboxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SandboxError::Denied(path) => write!(f, "access to {} is not allowed", path.display()),
+            SandboxError::MissingRoot(root) => {
+                write!(f, "the allowed directory {} doesn't exist", root.display())
+            }
+        }
+    }
+}
+impl Error for SandboxError {}
+
+/// checks that `path` is inside `root`, following symlinks as far as the path exists.
+/// without a root every path is allowed
+pub fn allowed(root: Option<&Path>, path: impl AsRef<Path>) -> Result<PathBuf, SandboxError> {
+    let path = path.as_ref();
+    let Some(root) = root else {
+        return Ok(path.to_path_buf());
+    };
+    let root = root
+        .canonicalize()
+        .map_err(|_| SandboxError::MissingRoot(root.to_path_buf()))?;
+    let absolute = env::current_dir().unwrap_or_default().join(path);
+    // resolve `..` by hand, since canonicalizing needs the whole path to exist
+    let mut normal = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::ParentDir => {
+                normal.pop();
+            }
+            Component::CurDir => {}
+            component => normal.push(component),
+        }
+    }
+    let mut existing = normal.as_path();
+    let mut rest = vec![];
+    let resolved = loop {
+        if let Ok(resolved) = existing.canonicalize() {
+            break rest.into_iter().rev().fold(resolved, |path, name| path.join(name));
+        }
+        let (Some(parent), Some(name)) = (existing.parent(), existing.file_name()) else {
+            return Err(SandboxError::Denied(path.to_path_buf()));
+        };
+        rest.push(name);
+        existing = parent;
+    };
+    if resolved.starts_with(&root) {
+        Ok(resolved)
+    } else {
+        Err(SandboxError::Denied(path.to_path_buf()))
+    }
+}
+
+/// a function of the `fs` module, given the checked path and the rest of the arguments
+type PathFunction = fn(&Path, Vec<Value>) -> Result<Value, Box<dyn Error>>;
+type NativeFunction = fn(&mut Interpreter, Vec<Value>) -> Result<Value, Box<dyn Error>>;
+
+/// the `fs` global, replacing luna's so every access goes through `allowed`
+pub fn fs_module(root: Option<PathBuf>) -> Value {
+    let root = Rc::new(root);
+    let function = |f: PathFunction| {
+        let root = Rc::clone(&root);
+        Value::Function(FunctionKind::UserFunction(Rc::new(move |_, args| {
+            let mut args = args.into_iter().enumerate();
+            let path = typed!(args: String);
+            let path = allowed(root.as_deref(), path)?;
+            f(&path, args.map(|(_, arg)| arg).collect())
+        })))
+    };
+    let wrapped = |f: NativeFunction| {
+        let root = Rc::clone(&root);
+        Value::Function(FunctionKind::UserFunction(Rc::new(move |interpreter, mut args| {
+            // hand on the checked path, since resolving the given one again could end up elsewhere
+            if let Some(Value::String(path)) = args.first_mut() {
+                let checked = allowed(root.as_deref(), &*path)?;
+                *path = checked
+                    .into_os_string()
+                    .into_string()
+                    .map_err(|checked| SandboxError::Denied(checked.into()))?;
+            }
+            f(interpreter, args)
+        })))
+    };
+    object! {
+        "read" = function(_read),
+        "write" = function(_write),
+        "read_bytes" = function(_read_bytes),
+        "list" = function(_list),
+        "exists" = function(_exists),
+        "mkdir" = function(_mkdir),
+        "open" = wrapped(_fs_open),
+        "type" = wrapped(_fs_type)
+    }
+}
+
+fn _read(path: &Path, _: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+    Ok(Value::String(fs::read_to_string(path)?))
+}
+fn _write(path: &Path, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+    // the path was argument #0
+    let mut args = args.into_iter().enumerate().map(|(idx, arg)| (idx + 1, arg));
+    let text = typed!(args: String);
+    fs::write(path, text)?;
+    Ok(Value::default())
+}
+fn _read_bytes(path: &Path, _: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+    let bytes = fs::read(path)?;
+    Ok(Value::Vector(Rc::new(RefCell::new(
+        bytes.into_iter().map(|byte| Value::Int(byte.into())).collect(),
+    ))))
+}
+fn _list(path: &Path, _: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+    let mut names = fs::read_dir(path)?
+        .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+        .collect::<Result<Vec<String>, std::io::Error>>()?;
+    names.sort();
+    Ok(names.into())
+}
+fn _exists(path: &Path, _: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+    Ok(Value::Bool(path.exists()))
+}
+fn _mkdir(path: &Path, _: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+    fs::create_dir_all(path)?;
+    Ok(Value::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    /// a directory with `root/data/save.txt` inside the sandbox root and `secret.txt` outside it
+    fn sandbox(name: &str) -> TempDir {
+        let dir = TempDir::new(name);
+        dir.write("root/data/save.txt", "save");
+        dir.write("secret.txt", "secret");
+        dir
+    }
+
+    fn call(module: &Value, name: &str, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Value::Object(module) = module else {
+            panic!("expected an object");
+        };
+        let Some(Value::Function(FunctionKind::UserFunction(function))) = module.borrow().get(name)
+        else {
+            panic!("expected fs.{name} to be a function");
+        };
+        function(&mut Interpreter::default(), args)
+    }
+
+    #[test]
+    fn everything_is_allowed_without_a_root() {
+        assert_eq!(allowed(None, "../x"), Ok(PathBuf::from("../x")));
+    }
+
+    #[test]
+    fn allows_paths_inside_the_root() {
+        let dir = sandbox("inside");
+        let root = dir.path().join("root");
+        assert_eq!(
+            allowed(Some(&root), root.join("data/save.txt")),
+            Ok(root.join("data/save.txt"))
+        );
+        // files that don't exist yet can still be created
+        assert_eq!(
+            allowed(Some(&root), root.join("data/new/file.txt")),
+            Ok(root.join("data/new/file.txt"))
+        );
+        assert_eq!(
+            allowed(Some(&root), root.join("data/../data/./save.txt")),
+            Ok(root.join("data/save.txt"))
+        );
+    }
+
+    #[test]
+    fn denies_parent_dirs_out_of_the_root() {
+        let dir = sandbox("parent");
+        let root = dir.path().join("root");
+        let path = root.join("data/../../secret.txt");
+        assert_eq!(allowed(Some(&root), &path), Err(SandboxError::Denied(path)));
+        let path = root.join("../root-sibling/file");
+        assert_eq!(allowed(Some(&root), &path), Err(SandboxError::Denied(path)));
+    }
+
+    #[test]
+    fn needs_an_existing_root() {
+        let dir = sandbox("missing");
+        let root = dir.path().join("missing");
+        assert_eq!(
+            allowed(Some(&root), root.join("file")),
+            Err(SandboxError::MissingRoot(root))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follows_symlinks() {
+        use std::os::unix::fs::symlink;
+        let dir = sandbox("symlinks");
+        let root = dir.path().join("root");
+        symlink(dir.path().join("secret.txt"), root.join("link.txt")).unwrap();
+        symlink(dir.path(), root.join("escape")).unwrap();
+        symlink(root.join("data"), root.join("alias")).unwrap();
+
+        let path = root.join("link.txt");
+        assert_eq!(allowed(Some(&root), &path), Err(SandboxError::Denied(path)));
+        let path = root.join("escape/new.txt");
+        assert_eq!(allowed(Some(&root), &path), Err(SandboxError::Denied(path)));
+        assert_eq!(
+            allowed(Some(&root), root.join("alias/save.txt")),
+            Ok(root.join("data/save.txt"))
+        );
+
+        let fs = fs_module(Some(root.clone()));
+        let path = Value::String(root.join("link.txt").display().to_string());
+        assert!(call(&fs, "read", vec![path.clone()]).is_err());
+        assert!(call(&fs, "type", vec![path]).is_err());
+        let path = Value::String(root.join("alias/save.txt").display().to_string());
+        assert_eq!(
+            call(&fs, "read", vec![path.clone()]).unwrap(),
+            Value::String("save".into())
+        );
+        assert_eq!(call(&fs, "type", vec![path]).unwrap(), Value::String("file".into()));
+    }
+}
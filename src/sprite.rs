@@ -0,0 +1,343 @@
+use crate::translation::{method_error, CanvasObject};
+use luna_rs::{
+    lang::value::{FunctionKind, UserObject, UserObjectError, Value},
+    luna_impl::interpreter::Interpreter,
+    option, typed, ExpectedType, ExpectedTypes,
+};
+use sdl2::rect::Rect;
+use std::{cell::RefCell, error::Error, rc::Rc};
+
+/// a texture cut into equally sized frames, numbered row by row from the top left
+#[derive(Clone)]
+pub struct SpritesheetObject {
+    canvas: CanvasObject,
+    /// keeps the texture alive as long as the sheet is
+    _texture: Value,
+    id: u64,
+    frame_width: u32,
+    frame_height: u32,
+    columns: u32,
+    count: u32,
+}
+impl UserObject for SpritesheetObject {
+    fn typ(&self) -> &'static str {
+        "spritesheet"
+    }
+    fn get(&self, key: &str) -> Option<Value> {
+        match key {
+            "count" => Some(Value::Int(self.count.into())),
+            "frame_width" => Some(Value::Int(self.frame_width.into())),
+            "frame_height" => Some(Value::Int(self.frame_height.into())),
+            "draw" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_draw,
+            )))),
+            "animation" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_animation,
+            )))),
+            _ => None,
+        }
+    }
+    fn call(&self, key: &str, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let given = args.clone();
+        match key {
+            "draw" => self.call_draw(args),
+            "animation" => self.call_animation(args),
+            _ => Err(UserObjectError::CannotCallNull.into()),
+        }
+        .map_err(|err| method_error(self.typ(), key, Self::params(key), &given, err))
+    }
+}
+impl SpritesheetObject {
+    pub fn new(
+        canvas: CanvasObject,
+        texture: Value,
+        id: u64,
+        frame_width: u32,
+        frame_height: u32,
+    ) -> Result<Self, Box<dyn Error>> {
+        let (width, height) = canvas.texture_size(id)?;
+        let columns = width / frame_width.max(1);
+        let count = columns * (height / frame_height.max(1));
+        if count == 0 {
+            return Err(format!(
+                "frames of {frame_width}x{frame_height} don't fit into a {width}x{height} texture"
+            )
+            .into());
+        }
+        Ok(Self {
+            canvas,
+            _texture: texture,
+            id,
+            frame_width,
+            frame_height,
+            columns,
+            count,
+        })
+    }
+    pub fn params(key: &str) -> &'static [&'static str] {
+        match key {
+            "draw" => &["index", "x", "y", "flip_h", "flip_v", "angle"],
+            "animation" => &["frames", "fps", "looping"],
+            _ => &[],
+        }
+    }
+    /// draws frame `index` at its own size with its top left corner at `(x, y)`
+    pub fn draw(
+        &self,
+        index: i64,
+        (x, y): (i32, i32),
+        flip: (bool, bool),
+        angle: f64,
+    ) -> Result<(), Box<dyn Error>> {
+        let frame = u32::try_from(index)
+            .ok()
+            .filter(|frame| *frame < self.count)
+            .ok_or_else(|| format!("frame #{index} doesn't exist, the sheet has {}", self.count))?;
+        let src = Rect::new(
+            ((frame % self.columns) * self.frame_width) as i32,
+            ((frame / self.columns) * self.frame_height) as i32,
+            self.frame_width,
+            self.frame_height,
+        );
+        let dst = Rect::new(x, y, self.frame_width, self.frame_height);
+        self.canvas.copy(self.id, Some(src), dst, (angle, None), flip)?;
+        Ok(())
+    }
+    pub fn _draw(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let _self = _self.borrow();
+            _self.call("draw", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    pub fn call_draw(&self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let mut args = args.into_iter().enumerate();
+        let index = typed!(args: Int);
+        let (position, flip, angle) = placement(&mut args)?;
+
+        self.draw(index, position, flip, angle)?;
+        Ok(Value::default())
+    }
+    pub fn _animation(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let _self = _self.borrow();
+            _self.call("animation", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    pub fn call_animation(&self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let mut args = args.into_iter().enumerate();
+        let frames = match typed!(args: Vector?) {
+            Some(frames) => frames
+                .borrow()
+                .iter()
+                .map(|frame| match frame {
+                    Value::Int(frame) => Ok(*frame),
+                    frame => Err(format!("expected int for a frame, got {}", frame.typ())),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None => (0..self.count.into()).collect(),
+        };
+        let fps = option!(args:
+            Int => int { int as f64 },
+            Float => float { float }
+        );
+        let looping = typed!(args: Bool?).unwrap_or(true);
+
+        if frames.is_empty() {
+            return Err("an animation needs at least one frame".into());
+        }
+        Ok(Value::UserObject(Rc::new(RefCell::new(Box::new(
+            AnimationObject {
+                sheet: self.clone(),
+                frames,
+                fps: fps.max(0.),
+                looping,
+                time: 0.,
+            },
+        )))))
+    }
+}
+
+/// plays frames of a sprite sheet at a fixed rate, advanced by `animation:update(dt)`
+pub struct AnimationObject {
+    sheet: SpritesheetObject,
+    frames: Vec<i64>,
+    fps: f64,
+    looping: bool,
+    /// seconds since the animation started
+    time: f64,
+}
+impl UserObject for AnimationObject {
+    fn typ(&self) -> &'static str {
+        "animation"
+    }
+    fn get(&self, key: &str) -> Option<Value> {
+        match key {
+            "frame" => Some(Value::Int(self.frame())),
+            "finished" => Some(Value::Bool(self.finished())),
+            "update" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_update,
+            )))),
+            "draw" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_draw,
+            )))),
+            "reset" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_reset,
+            )))),
+            _ => None,
+        }
+    }
+    fn call(&self, key: &str, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let given = args.clone();
+        match key {
+            "draw" => self.call_draw(args),
+            _ => Err(UserObjectError::CannotCallNull.into()),
+        }
+        .map_err(|err| method_error(self.typ(), key, Self::params(key), &given, err))
+    }
+    fn call_mut(&mut self, key: &str, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let given = args.clone();
+        match key {
+            "update" => self.call_update(args),
+            "reset" => self.call_reset(),
+            _ => return self.call(key, args),
+        }
+        .map_err(|err| method_error(self.typ(), key, Self::params(key), &given, err))
+    }
+}
+impl AnimationObject {
+    pub fn params(key: &str) -> &'static [&'static str] {
+        match key {
+            "update" => &["dt"],
+            "draw" => &["x", "y", "flip_h", "flip_v", "angle"],
+            _ => &[],
+        }
+    }
+    fn elapsed_frames(&self) -> usize {
+        (self.time * self.fps) as usize
+    }
+    /// the frame of the sheet that is currently shown
+    pub fn frame(&self) -> i64 {
+        let idx = if self.looping {
+            self.elapsed_frames() % self.frames.len()
+        } else {
+            self.elapsed_frames().min(self.frames.len() - 1)
+        };
+        self.frames[idx]
+    }
+    /// whether an animation that doesn't loop went past its last frame
+    pub fn finished(&self) -> bool {
+        !self.looping && self.elapsed_frames() >= self.frames.len()
+    }
+    pub fn _update(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let mut _self = _self.borrow_mut();
+            _self.call_mut("update", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    pub fn call_update(&mut self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let mut args = args.into_iter().enumerate();
+        let dt = option!(args:
+            Int => int { int as f64 },
+            Float => float { float }
+        );
+
+        self.time += dt.max(0.);
+        if self.looping && self.fps > 0. {
+            // keeps the time from growing until it loses precision
+            self.time %= self.frames.len() as f64 / self.fps;
+        }
+        Ok(Value::default())
+    }
+    pub fn _draw(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let _self = _self.borrow();
+            _self.call("draw", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    pub fn call_draw(&self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let mut args = args.into_iter().enumerate();
+        let (position, flip, angle) = placement(&mut args)?;
+
+        self.sheet.draw(self.frame(), position, flip, angle)?;
+        Ok(Value::default())
+    }
+    pub fn _reset(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let mut _self = _self.borrow_mut();
+            _self.call_mut("reset", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    pub fn call_reset(&mut self) -> Result<Value, Box<dyn Error>> {
+        self.time = 0.;
+        Ok(Value::default())
+    }
+}
+
+type Placement = ((i32, i32), (bool, bool), f64);
+/// the `x, y, [flip_h, flip_v, angle]` arguments both `draw` methods take
+fn placement<I: ExactSizeIterator<Item = (usize, Value)>>(
+    args: &mut I,
+) -> Result<Placement, Box<dyn Error>> {
+    let x = option!(args:
+        Int => int {
+            int.clamp(i32::MIN.into(), i32::MAX.into()).try_into()?
+        },
+        Float => float {
+            (float as i64).clamp(i32::MIN.into(), i32::MAX.into()).try_into()?
+        }
+    );
+    let y = option!(args:
+        Int => int {
+            int.clamp(i32::MIN.into(), i32::MAX.into()).try_into()?
+        },
+        Float => float {
+            (float as i64).clamp(i32::MIN.into(), i32::MAX.into()).try_into()?
+        }
+    );
+    let flip_h = typed!(args: Bool?).unwrap_or_default();
+    let flip_v = typed!(args: Bool?).unwrap_or_default();
+    let angle = match args.next() {
+        Some((_, Value::Int(angle))) => angle as f64,
+        Some((_, Value::Float(angle))) => angle,
+        None | Some((_, Value::Null)) => 0.,
+        Some((idx, arg)) => {
+            return Err(ExpectedTypes {
+                idx,
+                expected: vec!["int", "float"],
+                got: arg.typ(),
+            }
+            .into())
+        }
+    };
+    Ok(((x, y), (flip_h, flip_v), angle))
+}
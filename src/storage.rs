@@ -0,0 +1,165 @@
+use crate::json::{from_json, to_json};
+use luna_rs::{
+    lang::value::{FunctionKind, Object, Value},
+    object, typed, ExpectedType,
+};
+use serde_json::Map;
+use std::{cell::RefCell, collections::HashMap, env, error::Error, fs, path::PathBuf, rc::Rc};
+
+/// key-value save data of a script, kept in `storage.json` in a per-app directory under the user's
+/// data dir. it's read on first use and only written by `storage.save()`
+#[derive(Debug)]
+pub struct Storage {
+    path: Option<PathBuf>,
+    values: RefCell<Option<Map<String, serde_json::Value>>>,
+}
+impl Storage {
+    pub fn new(app: Option<&str>) -> Self {
+        // the name comes from the manifest and must not leave the data dir
+        let app = app.unwrap_or("default").replace(['/', '\\', '.'], "_");
+        Self {
+            path: data_dir().map(|dir| dir.join("deimos").join(&app).join("storage.json")),
+            values: RefCell::default(),
+        }
+    }
+    fn with_values<T>(
+        &self,
+        f: impl FnOnce(&mut Map<String, serde_json::Value>) -> T,
+    ) -> Result<T, Box<dyn Error>> {
+        let mut values = self.values.borrow_mut();
+        if values.is_none() {
+            *values = Some(self.load()?);
+        }
+        Ok(f(values.get_or_insert_with(Map::new)))
+    }
+    fn load(&self) -> Result<Map<String, serde_json::Value>, Box<dyn Error>> {
+        let Some(path) = self.path.as_ref().filter(|path| path.is_file()) else {
+            return Ok(Map::new());
+        };
+        let text = fs::read_to_string(path)?;
+        match serde_json::from_str(&text) {
+            Ok(serde_json::Value::Object(values)) => Ok(values),
+            _ => Err(format!("{} is not a json object", path.display()).into()),
+        }
+    }
+    pub fn get(&self, key: &str) -> Result<Value, Box<dyn Error>> {
+        self.with_values(|values| values.get(key).cloned().map(from_json).unwrap_or_default())
+    }
+    /// setting a key to `null` removes it
+    pub fn set(&self, key: String, value: &Value) -> Result<(), Box<dyn Error>> {
+        let value = to_json(value)?;
+        self.with_values(|values| match value {
+            serde_json::Value::Null => values.remove(&key),
+            value => values.insert(key, value),
+        })?;
+        Ok(())
+    }
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let path = self.path.as_ref().ok_or("couldn't find a directory for user data")?;
+        let text = self.with_values(|values| serde_json::to_string_pretty(values))??;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // write next to the save first, so a crash while saving can't lose the old one
+        let partial = path.with_extension("json.partial");
+        fs::write(&partial, text)?;
+        fs::rename(partial, path)?;
+        Ok(())
+    }
+}
+
+/// the `storage` global
+pub fn storage_module(storage: &Rc<Storage>) -> Value {
+    let get = Rc::clone(storage);
+    let set = Rc::clone(storage);
+    let save = Rc::clone(storage);
+    object! {
+        "get" = Value::Function(FunctionKind::UserFunction(Rc::new(move |_, args| {
+            let mut args = args.into_iter().enumerate();
+            let key = typed!(args: String);
+            get.get(&key)
+        }))),
+        "set" = Value::Function(FunctionKind::UserFunction(Rc::new(move |_, args| {
+            let mut args = args.into_iter().enumerate();
+            let key = typed!(args: String);
+            let (_, value) = args.next().unwrap_or_default();
+            set.set(key, &value)?;
+            Ok(Value::default())
+        }))),
+        "save" = Value::Function(FunctionKind::UserFunction(Rc::new(move |_, _| {
+            save.save()?;
+            Ok(Value::default())
+        })))
+    }
+}
+
+/// where applications keep their data on this platform
+pub fn data_dir() -> Option<PathBuf> {
+    let var = |name| env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+    if cfg!(windows) {
+        var("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        var("HOME").map(|home| home.join("Library/Application Support"))
+    } else {
+        var("XDG_DATA_HOME").or_else(|| var("HOME").map(|home| home.join(".local/share")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    fn storage(dir: &TempDir) -> Storage {
+        Storage {
+            path: Some(dir.path().join("game/storage.json")),
+            values: RefCell::default(),
+        }
+    }
+
+    #[test]
+    fn saves_and_loads_values() {
+        let dir = TempDir::new("storage");
+        let saved = storage(&dir);
+        assert_eq!(saved.get("score").unwrap(), Value::Null);
+        saved.set("score".into(), &Value::Int(10)).unwrap();
+        saved.set("name".into(), &"deimos".into()).unwrap();
+        saved.set("gone".into(), &Value::Bool(true)).unwrap();
+        saved.set("gone".into(), &Value::Null).unwrap();
+        // nothing is written before `save`
+        assert_eq!(storage(&dir).get("score").unwrap(), Value::Null);
+        saved.save().unwrap();
+        assert!(!dir.path().join("game/storage.json.partial").exists());
+
+        let loaded = storage(&dir);
+        assert_eq!(loaded.get("score").unwrap(), Value::Int(10));
+        assert_eq!(loaded.get("name").unwrap(), Value::String("deimos".into()));
+        assert_eq!(loaded.get("gone").unwrap(), Value::Null);
+        let text = fs::read_to_string(dir.path().join("game/storage.json")).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&text).unwrap(),
+            serde_json::json!({ "score": 10, "name": "deimos" })
+        );
+    }
+
+    #[test]
+    fn rejects_corrupt_saves() {
+        let dir = TempDir::new("storage-corrupt");
+        dir.write("game/storage.json", "[1, 2]");
+        let err = storage(&dir).get("score").unwrap_err().to_string();
+        assert!(err.ends_with("is not a json object"), "{err}");
+        // values that can't be saved are refused before touching the save
+        let storage = storage(&dir);
+        assert!(storage.set("nan".into(), &Value::Float(f64::NAN)).is_err());
+    }
+
+    #[test]
+    fn keeps_app_names_inside_the_data_dir() {
+        let storage = Storage::new(Some("../evil/.game"));
+        if let Some(path) = storage.path {
+            let app = path.parent().unwrap().file_name().unwrap();
+            assert_eq!(app, "___evil__game");
+            assert_eq!(path.parent().unwrap().parent().unwrap().file_name().unwrap(), "deimos");
+        }
+    }
+}
@@ -0,0 +1,1137 @@
+use crate::{sandbox::allowed, translation::Options};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use luna_rs::{
+    lang::value::{FunctionKind, Object, Value},
+    object, typed, ExpectedType,
+};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    error::Error,
+    fmt::Display,
+    fs,
+    io::Read,
+    path::{self, Path, PathBuf},
+    rc::Rc,
+    str::FromStr,
+};
+
+/// the bits of a gid that flip or rotate its tile, which are dropped since tilemaps can't do either
+const FLIP_FLAGS: u32 = 0xf000_0000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TiledError {
+    /// an attribute or field that's missing or not of the type it should be
+    Invalid(&'static str),
+    Unsupported(String),
+    Xml(&'static str),
+}
+impl Display for TiledError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TiledError::Invalid(field) => write!(f, "missing or invalid {field}"),
+            TiledError::Unsupported(what) => write!(f, "{what} are not supported"),
+            TiledError::Xml(problem) => write!(f, "invalid xml: {problem}"),
+        }
+    }
+}
+impl Error for TiledError {}
+
+/// a map made with the Tiled editor, read from a `.tmx` or `.tmj` file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Map {
+    pub width: u32,
+    pub height: u32,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    /// every layer, with the layers of groups in place of the group
+    pub layers: Vec<Layer>,
+    pub tilesets: Vec<Tileset>,
+    pub properties: Properties,
+}
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tileset {
+    /// the gid of the first tile of this tileset
+    pub first_gid: u32,
+    pub name: String,
+    /// the absolute path of the image the tiles are cut from
+    pub image: Option<PathBuf>,
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub columns: u32,
+    pub count: u32,
+    pub margin: u32,
+    pub spacing: u32,
+}
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layer {
+    pub name: String,
+    pub visible: bool,
+    pub opacity: f64,
+    pub offset: (f64, f64),
+    pub properties: Properties,
+    pub kind: LayerKind,
+}
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayerKind {
+    /// `gids` holds a gid for each tile, row by row, where 0 is empty
+    Tiles {
+        width: u32,
+        height: u32,
+        gids: Vec<u32>,
+    },
+    Objects(Vec<MapObject>),
+    Image(Option<PathBuf>),
+}
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapObject {
+    pub id: i64,
+    pub name: String,
+    pub class: String,
+    /// `rect`, `ellipse`, `point`, `polygon`, `polyline` or `tile`
+    pub shape: &'static str,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub rotation: f64,
+    pub visible: bool,
+    pub gid: Option<u32>,
+    /// the points of a polygon or polyline, relative to `(x, y)`
+    pub points: Vec<(f64, f64)>,
+    pub properties: Properties,
+}
+pub type Properties = Vec<(String, Value)>;
+
+impl Map {
+    /// reads a map, as json if the file ends in `.tmj` or `.json` and as xml otherwise. every file
+    /// it refers to has to be allowed by `root` as well
+    pub fn load(path: &Path, root: Option<&Path>) -> Result<Self, Box<dyn Error>> {
+        let text = fs::read_to_string(path)?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let map = if is_json(path) {
+            json_map(&serde_json::from_str(&text)?, dir, root)?
+        } else {
+            xml_map(&parse_xml(&text)?, dir, root)?
+        };
+        Ok(map)
+    }
+}
+fn is_json(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|ext| ext.to_str()),
+        Some("tmj" | "tsj" | "json")
+    )
+}
+/// the absolute path of `source`, which is relative to the file it's mentioned in and has to be
+/// allowed by `root`
+fn resolve(dir: &Path, source: &str, root: Option<&Path>) -> Result<PathBuf, Box<dyn Error>> {
+    Ok(allowed(root, path::absolute(dir.join(source))?)?)
+}
+/// reads the tileset in `source`, which starts at `first_gid` in the map
+fn external_tileset(
+    first_gid: u32,
+    dir: &Path,
+    source: &str,
+    root: Option<&Path>,
+) -> Result<Tileset, Box<dyn Error>> {
+    let path = allowed(root, dir.join(source))?;
+    let text = fs::read_to_string(&path)
+        .map_err(|err| format!("couldn't read the tileset {}: {err}", path.display()))?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    if is_json(&path) {
+        json_tileset(first_gid, &serde_json::from_str(&text)?, dir, root)
+    } else {
+        xml_tileset(first_gid, &parse_xml(&text)?, dir, root)
+    }
+}
+
+/// the gids of a layer stored as base64, possibly compressed
+fn decode_gids(text: &str, compression: Option<&str>) -> Result<Vec<u32>, Box<dyn Error>> {
+    let bytes = base64(text.trim()).ok_or(TiledError::Invalid("base64 tile data"))?;
+    let bytes = match compression {
+        None | Some("") => bytes,
+        Some("zlib") => {
+            let mut decoded = vec![];
+            ZlibDecoder::new(bytes.as_slice()).read_to_end(&mut decoded)?;
+            decoded
+        }
+        Some("gzip") => {
+            let mut decoded = vec![];
+            GzDecoder::new(bytes.as_slice()).read_to_end(&mut decoded)?;
+            decoded
+        }
+        Some(compression) => {
+            return Err(
+                TiledError::Unsupported(format!("layers compressed with {compression}")).into(),
+            )
+        }
+    };
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|gid| u32::from_le_bytes([gid[0], gid[1], gid[2], gid[3]]) & !FLIP_FLAGS)
+        .collect())
+}
+fn base64(text: &str) -> Option<Vec<u8>> {
+    let digit = |c: u8| match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    };
+    let digits = text
+        .bytes()
+        .filter(|c| !c.is_ascii_whitespace() && *c != b'=')
+        .map(digit)
+        .collect::<Option<Vec<_>>>()?;
+    let mut bytes = Vec::with_capacity(digits.len() * 3 / 4);
+    for chunk in digits.chunks(4) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, digit)| {
+            bits | u32::from(*digit) << (18 - 6 * i)
+        });
+        // 4 digits make 3 bytes, and every digit less makes one byte less
+        bytes.extend_from_slice(&bits.to_be_bytes()[1..chunk.len()]);
+    }
+    Some(bytes)
+}
+/// the value of a property, typed by its `type`
+fn property(typ: &str, value: &str) -> Value {
+    match typ {
+        "int" | "object" => value.parse().map(Value::Int).unwrap_or_default(),
+        "float" => value.parse().map(Value::Float).unwrap_or_default(),
+        "bool" => Value::Bool(value == "true"),
+        _ => Value::String(value.to_string()),
+    }
+}
+
+fn json_uint(json: &serde_json::Value, key: &'static str) -> Result<u32, TiledError> {
+    json.get(key)
+        .and_then(|value| value.as_u64())
+        .and_then(|value| value.try_into().ok())
+        .ok_or(TiledError::Invalid(key))
+}
+fn json_float(json: &serde_json::Value, key: &str) -> f64 {
+    json.get(key)
+        .and_then(|value| value.as_f64())
+        .unwrap_or_default()
+}
+fn json_str<'a>(json: &'a serde_json::Value, key: &str) -> &'a str {
+    json.get(key)
+        .and_then(|value| value.as_str())
+        .unwrap_or_default()
+}
+fn json_array<'a>(json: &'a serde_json::Value, key: &str) -> &'a [serde_json::Value] {
+    json.get(key)
+        .and_then(|value| value.as_array())
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+}
+fn json_properties(json: &serde_json::Value) -> Properties {
+    json_array(json, "properties")
+        .iter()
+        .map(|property| {
+            let value = match property.get("value") {
+                Some(serde_json::Value::String(value)) => {
+                    self::property(json_str(property, "type"), value)
+                }
+                Some(value) => crate::json::from_json(value.clone()),
+                None => Value::default(),
+            };
+            (json_str(property, "name").to_string(), value)
+        })
+        .collect()
+}
+fn json_map(
+    json: &serde_json::Value,
+    dir: &Path,
+    root: Option<&Path>,
+) -> Result<Map, Box<dyn Error>> {
+    if json.get("infinite").and_then(|infinite| infinite.as_bool()) == Some(true) {
+        return Err(TiledError::Unsupported("infinite maps".into()).into());
+    }
+    let mut layers = vec![];
+    json_layers(json, dir, root, &mut layers)?;
+    Ok(Map {
+        width: json_uint(json, "width")?,
+        height: json_uint(json, "height")?,
+        tile_width: json_uint(json, "tilewidth")?,
+        tile_height: json_uint(json, "tileheight")?,
+        layers,
+        tilesets: json_array(json, "tilesets")
+            .iter()
+            .map(|tileset| {
+                let first_gid = json_uint(tileset, "firstgid")?;
+                match tileset.get("source").and_then(|source| source.as_str()) {
+                    Some(source) => external_tileset(first_gid, dir, source, root),
+                    None => json_tileset(first_gid, tileset, dir, root),
+                }
+            })
+            .collect::<Result<_, _>>()?,
+        properties: json_properties(json),
+    })
+}
+fn json_tileset(
+    first_gid: u32,
+    json: &serde_json::Value,
+    dir: &Path,
+    root: Option<&Path>,
+) -> Result<Tileset, Box<dyn Error>> {
+    let image = match json.get("image").and_then(|image| image.as_str()) {
+        Some(image) => Some(resolve(dir, image, root)?),
+        None => None,
+    };
+    Ok(Tileset {
+        first_gid,
+        name: json_str(json, "name").to_string(),
+        image,
+        tile_width: json_uint(json, "tilewidth")?,
+        tile_height: json_uint(json, "tileheight")?,
+        columns: json_uint(json, "columns")?,
+        count: json_uint(json, "tilecount")?,
+        margin: json_uint(json, "margin").unwrap_or_default(),
+        spacing: json_uint(json, "spacing").unwrap_or_default(),
+    })
+}
+/// a layer of tiles, checking that there's a gid for every tile of a non-empty grid
+fn tile_layer(width: u32, height: u32, gids: Vec<u32>) -> Result<LayerKind, TiledError> {
+    if width == 0 || height == 0 {
+        return Err(TiledError::Invalid("layer size"));
+    }
+    if gids.len() as u64 != u64::from(width) * u64::from(height) {
+        return Err(TiledError::Invalid("tile data"));
+    }
+    Ok(LayerKind::Tiles {
+        width,
+        height,
+        gids,
+    })
+}
+/// appends the layers of a map or group to `layers`
+fn json_layers(
+    json: &serde_json::Value,
+    dir: &Path,
+    root: Option<&Path>,
+    layers: &mut Vec<Layer>,
+) -> Result<(), Box<dyn Error>> {
+    for layer in json_array(json, "layers") {
+        let kind = match json_str(layer, "type") {
+            "tilelayer" => {
+                let gids = match layer.get("data") {
+                    Some(serde_json::Value::String(data)) => {
+                        decode_gids(data, layer.get("compression").and_then(|c| c.as_str()))?
+                    }
+                    Some(serde_json::Value::Array(data)) => data
+                        .iter()
+                        .map(|gid| {
+                            let gid = u32::try_from(gid.as_u64()?).ok()?;
+                            Some(gid & !FLIP_FLAGS)
+                        })
+                        .collect::<Option<_>>()
+                        .ok_or(TiledError::Invalid("data"))?,
+                    _ => return Err(TiledError::Invalid("data").into()),
+                };
+                tile_layer(json_uint(layer, "width")?, json_uint(layer, "height")?, gids)?
+            }
+            "objectgroup" => LayerKind::Objects(
+                json_array(layer, "objects")
+                    .iter()
+                    .map(json_object)
+                    .collect(),
+            ),
+            "imagelayer" => {
+                LayerKind::Image(match layer.get("image").and_then(|image| image.as_str()) {
+                    Some(image) if !image.is_empty() => Some(resolve(dir, image, root)?),
+                    _ => None,
+                })
+            }
+            "group" => {
+                json_layers(layer, dir, root, layers)?;
+                continue;
+            }
+            typ => return Err(TiledError::Unsupported(format!("layers of type {typ:?}")).into()),
+        };
+        layers.push(Layer {
+            name: json_str(layer, "name").to_string(),
+            visible: layer
+                .get("visible")
+                .and_then(|visible| visible.as_bool())
+                .unwrap_or(true),
+            opacity: layer
+                .get("opacity")
+                .and_then(|opacity| opacity.as_f64())
+                .unwrap_or(1.),
+            offset: (json_float(layer, "offsetx"), json_float(layer, "offsety")),
+            properties: json_properties(layer),
+            kind,
+        });
+    }
+    Ok(())
+}
+fn json_object(json: &serde_json::Value) -> MapObject {
+    let flag = |key| json.get(key).and_then(|flag| flag.as_bool()) == Some(true);
+    let gid = json_uint(json, "gid").ok().map(|gid| gid & !FLIP_FLAGS);
+    let (shape, points) = if let Some(points) = json.get("polygon") {
+        ("polygon", points)
+    } else if let Some(points) = json.get("polyline") {
+        ("polyline", points)
+    } else if flag("ellipse") {
+        ("ellipse", &serde_json::Value::Null)
+    } else if flag("point") {
+        ("point", &serde_json::Value::Null)
+    } else if gid.is_some() {
+        ("tile", &serde_json::Value::Null)
+    } else {
+        ("rect", &serde_json::Value::Null)
+    };
+    let class = match json_str(json, "class") {
+        "" => json_str(json, "type"),
+        class => class,
+    };
+    MapObject {
+        id: json
+            .get("id")
+            .and_then(|id| id.as_i64())
+            .unwrap_or_default(),
+        name: json_str(json, "name").to_string(),
+        class: class.to_string(),
+        shape,
+        x: json_float(json, "x"),
+        y: json_float(json, "y"),
+        width: json_float(json, "width"),
+        height: json_float(json, "height"),
+        rotation: json_float(json, "rotation"),
+        visible: json
+            .get("visible")
+            .and_then(|visible| visible.as_bool())
+            .unwrap_or(true),
+        gid,
+        points: points
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|point| (json_float(point, "x"), json_float(point, "y")))
+            .collect(),
+        properties: json_properties(json),
+    }
+}
+
+/// an element of an xml document, with the text directly inside it
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Element {
+    name: String,
+    attributes: HashMap<String, String>,
+    children: Vec<Element>,
+    text: String,
+}
+impl Element {
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes.get(name).map(String::as_str)
+    }
+    fn parse<T: FromStr>(&self, name: &'static str) -> Result<Option<T>, TiledError> {
+        self.attr(name)
+            .map(|value| value.parse().map_err(|_| TiledError::Invalid(name)))
+            .transpose()
+    }
+    fn required<T: FromStr>(&self, name: &'static str) -> Result<T, TiledError> {
+        self.parse(name)?.ok_or(TiledError::Invalid(name))
+    }
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+}
+/// parses the subset of xml Tiled writes: elements, attributes, text, comments and cdata
+fn parse_xml(text: &str) -> Result<Element, TiledError> {
+    // the bottom element collects the root of the document
+    let mut stack = vec![Element::default()];
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        let parent = stack.last_mut().ok_or(TiledError::Xml("unbalanced tags"))?;
+        parent.text.push_str(&unescape(&rest[..start]));
+        rest = &rest[start..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            let end = comment
+                .find("-->")
+                .ok_or(TiledError::Xml("unclosed comment"))?;
+            rest = &comment[end + 3..];
+            continue;
+        }
+        if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+            let end = cdata.find("]]>").ok_or(TiledError::Xml("unclosed cdata"))?;
+            parent.text.push_str(&cdata[..end]);
+            rest = &cdata[end + 3..];
+            continue;
+        }
+        let end = tag_end(rest).ok_or(TiledError::Xml("unclosed tag"))?;
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+        if let Some(name) = tag.strip_prefix('/') {
+            let element = stack.pop().ok_or(TiledError::Xml("unbalanced tags"))?;
+            if element.name != name.trim() {
+                return Err(TiledError::Xml("unbalanced tags"));
+            }
+            stack
+                .last_mut()
+                .ok_or(TiledError::Xml("unbalanced tags"))?
+                .children
+                .push(element);
+        } else if let Some(tag) = tag.strip_suffix('/') {
+            parent.children.push(parse_tag(tag)?);
+        } else {
+            stack.push(parse_tag(tag)?);
+        }
+    }
+    match stack.pop() {
+        Some(document) if stack.is_empty() => document
+            .children
+            .into_iter()
+            .next()
+            .ok_or(TiledError::Xml("empty document")),
+        _ => Err(TiledError::Xml("unclosed element")),
+    }
+}
+/// the index of the `>` closing the tag at the start of `text`, skipping quoted attribute values
+fn tag_end(text: &str) -> Option<usize> {
+    let mut quote = None;
+    for (idx, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), c) if c == open => quote = None,
+            (None, '>') => return Some(idx),
+            _ => {}
+        }
+    }
+    None
+}
+fn parse_tag(tag: &str) -> Result<Element, TiledError> {
+    let tag = tag.trim();
+    let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+    let mut element = Element {
+        name: tag[..name_end].to_string(),
+        ..Default::default()
+    };
+    let mut rest = tag[name_end..].trim_start();
+    while !rest.is_empty() {
+        let (name, value) = rest
+            .split_once('=')
+            .ok_or(TiledError::Xml("attribute without a value"))?;
+        let value = value.trim_start();
+        let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'');
+        let quote = quote.ok_or(TiledError::Xml("unquoted attribute value"))?;
+        let end = value[1..]
+            .find(quote)
+            .ok_or(TiledError::Xml("unclosed attribute value"))?;
+        element
+            .attributes
+            .insert(name.trim().to_string(), unescape(&value[1..end + 1]));
+        rest = value[end + 2..].trim_start();
+    }
+    Ok(element)
+}
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+        let c = match &rest[1..end] {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            entity => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match c {
+            Some(c) => {
+                unescaped.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+fn xml_properties(element: &Element) -> Properties {
+    let Some(properties) = element.child("properties") else {
+        return vec![];
+    };
+    properties
+        .children
+        .iter()
+        .filter(|property| property.name == "property")
+        .map(|property| {
+            // multiline strings are written as text instead of an attribute
+            let value = property.attr("value").unwrap_or(&property.text);
+            let name = property.attr("name").unwrap_or_default().to_string();
+            (
+                name,
+                self::property(property.attr("type").unwrap_or("string"), value),
+            )
+        })
+        .collect()
+}
+fn xml_map(element: &Element, dir: &Path, root: Option<&Path>) -> Result<Map, Box<dyn Error>> {
+    if element.name != "map" {
+        return Err(TiledError::Xml("expected a map").into());
+    }
+    if element.parse::<u8>("infinite")? == Some(1) {
+        return Err(TiledError::Unsupported("infinite maps".into()).into());
+    }
+    let mut layers = vec![];
+    xml_layers(element, dir, root, &mut layers)?;
+    Ok(Map {
+        width: element.required("width")?,
+        height: element.required("height")?,
+        tile_width: element.required("tilewidth")?,
+        tile_height: element.required("tileheight")?,
+        layers,
+        tilesets: element
+            .children
+            .iter()
+            .filter(|child| child.name == "tileset")
+            .map(|tileset| {
+                let first_gid = tileset.required("firstgid")?;
+                match tileset.attr("source") {
+                    Some(source) => external_tileset(first_gid, dir, source, root),
+                    None => xml_tileset(first_gid, tileset, dir, root),
+                }
+            })
+            .collect::<Result<_, _>>()?,
+        properties: xml_properties(element),
+    })
+}
+fn xml_tileset(
+    first_gid: u32,
+    element: &Element,
+    dir: &Path,
+    root: Option<&Path>,
+) -> Result<Tileset, Box<dyn Error>> {
+    let image = match element
+        .child("image")
+        .and_then(|image| image.attr("source"))
+    {
+        Some(image) => Some(resolve(dir, image, root)?),
+        None => None,
+    };
+    Ok(Tileset {
+        first_gid,
+        name: element.attr("name").unwrap_or_default().to_string(),
+        image,
+        tile_width: element.required("tilewidth")?,
+        tile_height: element.required("tileheight")?,
+        columns: element.required("columns")?,
+        count: element.required("tilecount")?,
+        margin: element.parse("margin")?.unwrap_or_default(),
+        spacing: element.parse("spacing")?.unwrap_or_default(),
+    })
+}
+fn xml_layers(
+    element: &Element,
+    dir: &Path,
+    root: Option<&Path>,
+    layers: &mut Vec<Layer>,
+) -> Result<(), Box<dyn Error>> {
+    for layer in &element.children {
+        let kind = match layer.name.as_str() {
+            "layer" => {
+                let data = layer.child("data").ok_or(TiledError::Invalid("data"))?;
+                if data.child("chunk").is_some() {
+                    return Err(TiledError::Unsupported("infinite maps".into()).into());
+                }
+                let gids = match data.attr("encoding") {
+                    Some("csv") => data
+                        .text
+                        .split(',')
+                        .map(|gid| gid.trim().parse::<u32>().map(|gid| gid & !FLIP_FLAGS))
+                        .collect::<Result<_, _>>()
+                        .map_err(|_| TiledError::Invalid("csv tile data"))?,
+                    Some("base64") => decode_gids(&data.text, data.attr("compression"))?,
+                    Some(encoding) => {
+                        return Err(TiledError::Unsupported(format!(
+                            "layers encoded as {encoding}"
+                        ))
+                        .into())
+                    }
+                    None => data
+                        .children
+                        .iter()
+                        .filter(|tile| tile.name == "tile")
+                        .map(|tile| Ok(tile.parse::<u32>("gid")?.unwrap_or_default() & !FLIP_FLAGS))
+                        .collect::<Result<_, TiledError>>()?,
+                };
+                tile_layer(layer.required("width")?, layer.required("height")?, gids)?
+            }
+            "objectgroup" => LayerKind::Objects(
+                layer
+                    .children
+                    .iter()
+                    .filter(|object| object.name == "object")
+                    .map(xml_object)
+                    .collect::<Result<_, _>>()?,
+            ),
+            "imagelayer" => LayerKind::Image(
+                match layer.child("image").and_then(|image| image.attr("source")) {
+                    Some(image) if !image.is_empty() => Some(resolve(dir, image, root)?),
+                    _ => None,
+                },
+            ),
+            "group" => {
+                xml_layers(layer, dir, root, layers)?;
+                continue;
+            }
+            _ => continue,
+        };
+        layers.push(Layer {
+            name: layer.attr("name").unwrap_or_default().to_string(),
+            visible: layer.parse::<u8>("visible")?.unwrap_or(1) != 0,
+            opacity: layer.parse("opacity")?.unwrap_or(1.),
+            offset: (
+                layer.parse("offsetx")?.unwrap_or_default(),
+                layer.parse("offsety")?.unwrap_or_default(),
+            ),
+            properties: xml_properties(layer),
+            kind,
+        });
+    }
+    Ok(())
+}
+fn xml_object(element: &Element) -> Result<MapObject, TiledError> {
+    let gid = element.parse::<u32>("gid")?.map(|gid| gid & !FLIP_FLAGS);
+    let (shape, points) = if let Some(polygon) = element.child("polygon") {
+        ("polygon", polygon.attr("points"))
+    } else if let Some(polyline) = element.child("polyline") {
+        ("polyline", polyline.attr("points"))
+    } else if element.child("ellipse").is_some() {
+        ("ellipse", None)
+    } else if element.child("point").is_some() {
+        ("point", None)
+    } else if gid.is_some() {
+        ("tile", None)
+    } else {
+        ("rect", None)
+    };
+    let points = points
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(|point| {
+            let (x, y) = point.split_once(',')?;
+            Some((x.parse().ok()?, y.parse().ok()?))
+        })
+        .collect::<Option<_>>()
+        .ok_or(TiledError::Invalid("points"))?;
+    Ok(MapObject {
+        id: element.parse("id")?.unwrap_or_default(),
+        name: element.attr("name").unwrap_or_default().to_string(),
+        class: element
+            .attr("class")
+            .or(element.attr("type"))
+            .unwrap_or_default()
+            .to_string(),
+        shape,
+        x: element.parse("x")?.unwrap_or_default(),
+        y: element.parse("y")?.unwrap_or_default(),
+        width: element.parse("width")?.unwrap_or_default(),
+        height: element.parse("height")?.unwrap_or_default(),
+        rotation: element.parse("rotation")?.unwrap_or_default(),
+        visible: element.parse::<u8>("visible")?.unwrap_or(1) != 0,
+        gid,
+        points,
+        properties: xml_properties(element),
+    })
+}
+
+fn properties_value(properties: &Properties) -> Value {
+    properties.iter().cloned().collect::<HashMap<_, _>>().into()
+}
+fn path_value(path: &Option<PathBuf>) -> Value {
+    path.as_ref()
+        .map(|path| Value::String(path.display().to_string()))
+        .unwrap_or_default()
+}
+fn object_value(object: &MapObject) -> Value {
+    object! {
+        "id" = object.id,
+        "name" = object.name.clone(),
+        "class" = object.class.clone(),
+        "shape" = object.shape,
+        "x" = object.x,
+        "y" = object.y,
+        "width" = object.width,
+        "height" = object.height,
+        "rotation" = object.rotation,
+        "visible" = object.visible,
+        "gid" = object.gid.map(Value::from).unwrap_or_default(),
+        "points" = object
+            .points
+            .iter()
+            .map(|(x, y)| object! { "x" = *x, "y" = *y })
+            .collect::<Vec<_>>(),
+        "properties" = properties_value(&object.properties)
+    }
+}
+/// the map as a luna object. with a canvas, every tile layer also gets a `tilemap` of that canvas
+fn map_value(map: &Map, canvas: Option<&Value>) -> Result<Value, Box<dyn Error>> {
+    let mut textures = HashMap::new();
+    let mut layers = vec![];
+    let mut object_groups = vec![];
+    for layer in &map.layers {
+        let value = object! {
+            "name" = layer.name.clone(),
+            "visible" = layer.visible,
+            "opacity" = layer.opacity,
+            "offset_x" = layer.offset.0,
+            "offset_y" = layer.offset.1,
+            "properties" = properties_value(&layer.properties)
+        };
+        let Value::Object(fields) = &value else {
+            unreachable!("object! makes an object");
+        };
+        let mut fields = fields.borrow_mut();
+        match &layer.kind {
+            LayerKind::Tiles {
+                width,
+                height,
+                gids,
+            } => {
+                fields.set("kind".into(), "tiles".into());
+                fields.set("width".into(), (*width).into());
+                fields.set("height".into(), (*height).into());
+                fields.set("data".into(), gids.clone().into());
+                if let Some(canvas) = canvas {
+                    let tilemap = tilemap(map, layer, canvas, &mut textures)
+                        .map_err(|err| format!("layer {:?}: {err}", layer.name))?;
+                    fields.set("tilemap".into(), tilemap);
+                }
+            }
+            LayerKind::Objects(objects) => {
+                fields.set("kind".into(), "objects".into());
+                fields.set(
+                    "objects".into(),
+                    objects.iter().map(object_value).collect::<Vec<_>>().into(),
+                );
+                object_groups.push(value.clone());
+            }
+            LayerKind::Image(image) => {
+                fields.set("kind".into(), "image".into());
+                fields.set("image".into(), path_value(image));
+            }
+        }
+        drop(fields);
+        layers.push(value);
+    }
+    let tilesets = map
+        .tilesets
+        .iter()
+        .map(|tileset| {
+            object! {
+                "first_gid" = tileset.first_gid,
+                "name" = tileset.name.clone(),
+                "image" = path_value(&tileset.image),
+                "tile_width" = tileset.tile_width,
+                "tile_height" = tileset.tile_height,
+                "columns" = tileset.columns,
+                "count" = tileset.count,
+                "margin" = tileset.margin,
+                "spacing" = tileset.spacing
+            }
+        })
+        .collect::<Vec<_>>();
+    Ok(object! {
+        "width" = map.width,
+        "height" = map.height,
+        "tile_width" = map.tile_width,
+        "tile_height" = map.tile_height,
+        "layers" = layers,
+        "object_groups" = object_groups,
+        "tilesets" = tilesets,
+        "properties" = properties_value(&map.properties)
+    })
+}
+/// makes a tilemap of `canvas` with the tiles of a layer, drawn from the one tileset they all come
+/// from. `textures` holds the tileset images loaded so far by their first gid
+fn tilemap(
+    map: &Map,
+    layer: &Layer,
+    canvas: &Value,
+    textures: &mut HashMap<u32, Value>,
+) -> Result<Value, Box<dyn Error>> {
+    let LayerKind::Tiles {
+        width,
+        height,
+        gids,
+    } = &layer.kind
+    else {
+        return Ok(Value::default());
+    };
+    let Value::UserObject(canvas) = canvas else {
+        return Ok(Value::default());
+    };
+    let tileset_of = |gid: u32| {
+        map.tilesets
+            .iter()
+            .rev()
+            .find(|tileset| tileset.first_gid <= gid)
+    };
+    let tileset = gids
+        .iter()
+        .find(|gid| **gid != 0)
+        .and_then(|gid| tileset_of(*gid));
+    let mut args = vec![Value::from(*width), Value::from(*height)];
+    match tileset {
+        Some(tileset) => {
+            if tileset.tile_width != tileset.tile_height {
+                return Err(
+                    TiledError::Unsupported("tilesets of tiles that aren't square".into()).into(),
+                );
+            }
+            if tileset.margin != 0 || tileset.spacing != 0 {
+                return Err(
+                    TiledError::Unsupported("tilesets with a margin or spacing".into()).into(),
+                );
+            }
+            let image = tileset
+                .image
+                .as_ref()
+                .ok_or_else(|| TiledError::Unsupported("tilesets without an image".into()))?;
+            let texture = match textures.get(&tileset.first_gid) {
+                Some(texture) => texture.clone(),
+                None => {
+                    let texture = canvas
+                        .borrow_mut()
+                        .call_mut("texture", vec![image.display().to_string().into()])?;
+                    textures.insert(tileset.first_gid, texture.clone());
+                    texture
+                }
+            };
+            args.push(tileset.tile_width.into());
+            args.push(texture);
+        }
+        None => args.push(map.tile_width.into()),
+    }
+    let tilemap = canvas.borrow_mut().call_mut("tilemap", args)?;
+    let Value::UserObject(object) = &tilemap else {
+        return Ok(Value::default());
+    };
+    let mut object = object.borrow_mut();
+    for (idx, gid) in gids.iter().enumerate().filter(|(_, gid)| **gid != 0) {
+        let (first_gid, count) = tileset
+            .map(|tileset| (tileset.first_gid, tileset.count))
+            .unwrap_or_default();
+        if tileset_of(*gid).map(|other| other.first_gid) != Some(first_gid)
+            || *gid - first_gid >= count
+        {
+            return Err("tiles from more than one tileset can't be drawn by one tilemap".into());
+        }
+        let (x, y) = (idx as u32 % width, idx as u32 / width);
+        object.call_mut(
+            "set",
+            vec![x.into(), y.into(), Value::from(*gid - first_gid + 1)],
+        )?;
+    }
+    drop(object);
+    Ok(tilemap)
+}
+
+/// the `tiled` global
+pub fn tiled_module(options: &Rc<Options>) -> Value {
+    let options = Rc::clone(options);
+    object! {
+        "load" = Value::Function(FunctionKind::UserFunction(Rc::new(move |_, args| {
+            let mut args = args.into_iter().enumerate();
+            let path = typed!(args: String);
+            let canvas = match args.next() {
+                None | Some((_, Value::Null)) => None,
+                Some((_, Value::UserObject(canvas))) if canvas.borrow().typ() == "canvas" => {
+                    Some(Value::UserObject(canvas))
+                }
+                Some((idx, arg)) => {
+                    return Err(ExpectedType {
+                        idx,
+                        expected: "canvas",
+                        got: arg.typ(),
+                    }
+                    .into())
+                }
+            };
+            let path = options.asset(&path)?;
+            let map = Map::load(&path, options.allow_fs.as_deref())
+                .map_err(|err| format!("couldn't load the map {}: {err}", path.display()))?;
+            map_value(&map, canvas.as_ref())
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    /// the gids 1, 2, a flipped 3 and 0 as little endian bytes
+    const GIDS: &str = "AQAAAAIAAAADAACAAAAAAA==";
+    const ZLIB_GIDS: &str = "eJxjZGBgYAJiZgaGBiDFAAAC0ACH";
+    const GZIP_GIDS: &str = "H4sIAAAAAAACA2NkYGBgAmJmBoYGIMUAACrzgZEQAAAA";
+
+    fn map_xml(layer: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<map width="2" height="2" tilewidth="16" tileheight="16">{layer}</map>"#
+        )
+    }
+    fn tiles(text: &str) -> Result<LayerKind, Box<dyn Error>> {
+        let map = xml_map(&parse_xml(text)?, Path::new(""), None)?;
+        Ok(map.layers[0].kind.clone())
+    }
+
+    #[test]
+    fn parses_nested_elements() {
+        let root = parse_xml(
+            r#"<?xml version="1.0"?>
+<!-- a comment with <tags> -->
+<map name='a > b' empty="">
+    <layer id="1"/>
+    <text>one &amp; <![CDATA[<two>]]></text>
+</map>"#,
+        )
+        .unwrap();
+        assert_eq!(root.name, "map");
+        assert_eq!(root.attr("name"), Some("a > b"));
+        assert_eq!(root.attr("empty"), Some(""));
+        assert_eq!(root.children.len(), 2);
+        assert_eq!(root.children[0].name, "layer");
+        assert_eq!(root.children[0].parse::<u32>("id"), Ok(Some(1)));
+        assert_eq!(root.child("text").unwrap().text, "one & <two>");
+    }
+
+    #[test]
+    fn rejects_broken_xml() {
+        assert_eq!(
+            parse_xml("<map><layer></map>"),
+            Err(TiledError::Xml("unbalanced tags"))
+        );
+        assert_eq!(parse_xml("</map>"), Err(TiledError::Xml("unbalanced tags")));
+        assert_eq!(parse_xml("<map>"), Err(TiledError::Xml("unclosed element")));
+        assert_eq!(parse_xml("<map"), Err(TiledError::Xml("unclosed tag")));
+        assert_eq!(parse_xml("<!-- <map/>"), Err(TiledError::Xml("unclosed comment")));
+        assert_eq!(parse_xml("just text"), Err(TiledError::Xml("empty document")));
+    }
+
+    #[test]
+    fn unescapes_entities() {
+        assert_eq!(unescape("&lt;a&gt; &amp; &quot;b&quot; &apos;c&apos;"), "<a> & \"b\" 'c'");
+        assert_eq!(unescape("&#65;&#x42;&#x63;"), "ABc");
+        assert_eq!(unescape("&unknown; & alone"), "&unknown; & alone");
+    }
+
+    #[test]
+    fn decodes_base64_gids() {
+        assert_eq!(decode_gids(GIDS, None).unwrap(), [1, 2, 3, 0]);
+        assert_eq!(decode_gids(&format!("\n  {GIDS}\n"), Some("")).unwrap(), [1, 2, 3, 0]);
+        assert_eq!(decode_gids(ZLIB_GIDS, Some("zlib")).unwrap(), [1, 2, 3, 0]);
+        assert_eq!(decode_gids(GZIP_GIDS, Some("gzip")).unwrap(), [1, 2, 3, 0]);
+        assert!(decode_gids(GIDS, Some("zstd")).is_err());
+        assert!(decode_gids("not base64!", None).is_err());
+    }
+
+    #[test]
+    fn decodes_layer_data() {
+        let expected = LayerKind::Tiles {
+            width: 2,
+            height: 2,
+            gids: vec![1, 2, 3, 0],
+        };
+        let csv = map_xml(
+            r#"<layer width="2" height="2"><data encoding="csv">
+1,2,
+2147483651,0
+</data></layer>"#,
+        );
+        assert_eq!(tiles(&csv).unwrap(), expected);
+        let base64 = map_xml(&format!(
+            r#"<layer width="2" height="2"><data encoding="base64" compression="zlib">{ZLIB_GIDS}</data></layer>"#
+        ));
+        assert_eq!(tiles(&base64).unwrap(), expected);
+        let elements = map_xml(
+            r#"<layer width="2" height="2"><data>
+<tile gid="1"/><tile gid="2"/><tile gid="3"/><tile/>
+</data></layer>"#,
+        );
+        assert_eq!(tiles(&elements).unwrap(), expected);
+    }
+
+    #[test]
+    fn rejects_bad_layer_data() {
+        let error = |layer: &str| tiles(&map_xml(layer)).unwrap_err().to_string();
+        assert_eq!(
+            error(r#"<layer width="2" height="2"><data encoding="csv">1,x,3,4</data></layer>"#),
+            TiledError::Invalid("csv tile data").to_string()
+        );
+        assert_eq!(
+            error(r#"<layer width="2" height="2"><data encoding="csv">1,2,3</data></layer>"#),
+            TiledError::Invalid("tile data").to_string()
+        );
+        assert_eq!(
+            error(r#"<layer width="0" height="2"><data/></layer>"#),
+            TiledError::Invalid("layer size").to_string()
+        );
+    }
+
+    #[test]
+    fn keeps_referenced_files_inside_the_root() {
+        let dir = TempDir::new("tiled");
+        let root = dir.path().join("root");
+        let image_layer = r#"<imagelayer><image source="../secret.png"/></imagelayer>"#;
+        let tileset = r#"<tileset firstgid="1" source="../secret.tsx"/>"#;
+        dir.write("secret.tsx", r#"<tileset tilewidth="16" tileheight="16" columns="1" tilecount="1"/>"#);
+        for (name, layer) in [("image.tmx", image_layer), ("tileset.tmx", tileset)] {
+            dir.write(&format!("root/{name}"), &map_xml(layer));
+            let path = root.join(name);
+            assert!(Map::load(&path, None).is_ok(), "{name} didn't load without a root");
+            assert!(Map::load(&path, Some(&root)).is_err(), "{name} left the root");
+        }
+        dir.write("root/ok.tmx", &map_xml(r#"<imagelayer><image source="bg.png"/></imagelayer>"#));
+        let map = Map::load(&root.join("ok.tmx"), Some(&root)).unwrap();
+        assert_eq!(map.layers[0].kind, LayerKind::Image(Some(root.join("bg.png"))));
+    }
+
+    #[test]
+    fn rejects_json_gids_out_of_range() {
+        let map = |data: &str| {
+            let json = format!(
+                r#"{{"width": 2, "height": 1, "tilewidth": 16, "tileheight": 16, "layers": [
+                    {{"type": "tilelayer", "width": 2, "height": 1, "data": {data}}}
+                ]}}"#
+            );
+            json_map(&serde_json::from_str(&json).unwrap(), Path::new(""), None)
+        };
+        let layer = map("[1, 2147483651]").unwrap().layers.remove(0);
+        assert_eq!(
+            layer.kind,
+            LayerKind::Tiles {
+                width: 2,
+                height: 1,
+                gids: vec![1, 3],
+            }
+        );
+        let error = map("[1, 4294967297]").unwrap_err().to_string();
+        assert_eq!(error, TiledError::Invalid("data").to_string());
+        assert!(map("[1, -1]").is_err());
+    }
+}
@@ -0,0 +1,294 @@
+use crate::translation::{method_error, CanvasObject};
+use luna_rs::{
+    lang::value::{FunctionKind, UserObject, UserObjectError, Value},
+    luna_impl::interpreter::Interpreter,
+    option, typed, ExpectedType, ExpectedTypes,
+};
+use sdl2::rect::Rect;
+use std::{error::Error, rc::Rc};
+
+/// the most tiles a map may have, which keeps a typo in its size from taking all the memory
+const MAX_TILES: u64 = 1 << 24;
+
+/// the texture a tilemap draws its tiles from, cut into squares of the tile size
+pub struct Tileset {
+    /// keeps the texture alive as long as the map is
+    _texture: Value,
+    id: u64,
+    columns: u32,
+    count: u32,
+}
+
+/// a grid of tile ids, where 0 is empty and `n` is the `n`th tile of the tileset, counted row by
+/// row from the top left. without a tileset every tile that isn't empty is filled with the draw color
+pub struct TilemapObject {
+    canvas: CanvasObject,
+    tileset: Option<Tileset>,
+    width: u32,
+    height: u32,
+    tile_size: u32,
+    tiles: Vec<u32>,
+}
+impl UserObject for TilemapObject {
+    fn typ(&self) -> &'static str {
+        "tilemap"
+    }
+    fn get(&self, key: &str) -> Option<Value> {
+        match key {
+            "width" => Some(Value::Int(self.width.into())),
+            "height" => Some(Value::Int(self.height.into())),
+            "tile_size" => Some(Value::Int(self.tile_size.into())),
+            "set" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_set,
+            )))),
+            "get" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_get,
+            )))),
+            "draw" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_draw,
+            )))),
+            _ => None,
+        }
+    }
+    fn call(&self, key: &str, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let given = args.clone();
+        match key {
+            "get" => self.call_get(args),
+            "draw" => self.call_draw(args),
+            _ => Err(UserObjectError::CannotCallNull.into()),
+        }
+        .map_err(|err| method_error(self.typ(), key, Self::params(key), &given, err))
+    }
+    fn call_mut(&mut self, key: &str, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let given = args.clone();
+        match key {
+            "set" => self.call_set(args),
+            _ => return self.call(key, args),
+        }
+        .map_err(|err| method_error(self.typ(), key, Self::params(key), &given, err))
+    }
+}
+impl TilemapObject {
+    pub fn new(
+        canvas: CanvasObject,
+        width: u32,
+        height: u32,
+        tile_size: u32,
+        tileset: Option<(Value, u64)>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let len = u64::from(width) * u64::from(height);
+        if len > MAX_TILES {
+            return Err(format!("a {width}x{height} tilemap has more than {MAX_TILES} tiles").into());
+        }
+        let tileset = match tileset {
+            Some((texture, id)) => {
+                let (texture_width, texture_height) = canvas.texture_size(id)?;
+                let columns = texture_width / tile_size;
+                let count = columns * (texture_height / tile_size);
+                if count == 0 {
+                    return Err(format!(
+                        "tiles of {tile_size}x{tile_size} don't fit into a {texture_width}x{texture_height} texture"
+                    )
+                    .into());
+                }
+                Some(Tileset {
+                    _texture: texture,
+                    id,
+                    columns,
+                    count,
+                })
+            }
+            None => None,
+        };
+        Ok(Self {
+            canvas,
+            tileset,
+            width,
+            height,
+            tile_size,
+            tiles: vec![0; usize::try_from(len)?],
+        })
+    }
+    pub fn params(key: &str) -> &'static [&'static str] {
+        match key {
+            "set" => &["x", "y", "id"],
+            "get" => &["x", "y"],
+            "draw" => &["canvas", "offset_x", "offset_y"],
+            _ => &[],
+        }
+    }
+    /// the index of the tile at `(x, y)` in `tiles`, if it's on the map
+    fn index(&self, x: i64, y: i64) -> Option<usize> {
+        let x = u32::try_from(x).ok().filter(|x| *x < self.width)?;
+        let y = u32::try_from(y).ok().filter(|y| *y < self.height)?;
+        Some(y as usize * self.width as usize + x as usize)
+    }
+    pub fn _set(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let mut _self = _self.borrow_mut();
+            _self.call_mut("set", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    pub fn call_set(&mut self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let mut args = args.into_iter().enumerate();
+        let x = typed!(args: Int);
+        let y = typed!(args: Int);
+        let id = typed!(args: Int);
+
+        let idx = self.index(x, y).ok_or_else(|| {
+            format!(
+                "({x}, {y}) is outside of the {}x{} map",
+                self.width, self.height
+            )
+        })?;
+        let id = u32::try_from(id)
+            .ok()
+            .filter(|id| {
+                self.tileset
+                    .as_ref()
+                    .is_none_or(|tileset| *id <= tileset.count)
+            })
+            .ok_or_else(|| match &self.tileset {
+                Some(tileset) => format!(
+                    "tile #{id} doesn't exist, the tileset has {}",
+                    tileset.count
+                ),
+                None => format!("tile #{id} doesn't exist"),
+            })?;
+        self.tiles[idx] = id;
+        Ok(Value::default())
+    }
+    pub fn _get(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let _self = _self.borrow();
+            _self.call("get", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    /// the id of the tile at `(x, y)`, or null outside of the map
+    pub fn call_get(&self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let mut args = args.into_iter().enumerate();
+        let x = typed!(args: Int);
+        let y = typed!(args: Int);
+
+        Ok(self
+            .index(x, y)
+            .map(|idx| Value::Int(self.tiles[idx].into()))
+            .unwrap_or_default())
+    }
+    pub fn _draw(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let _self = _self.borrow();
+            _self.call("draw", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    /// draws the tiles that end up inside the canvas, with the top left corner of the map at
+    /// `(offset_x, offset_y)`. the canvas has to be the one the map was made by
+    pub fn call_draw(&self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let mut args = args.into_iter().enumerate();
+        let (idx, canvas) = args.next().unwrap_or_default();
+        let canvas_id = match &canvas {
+            Value::UserObject(object) if object.borrow().typ() == "canvas" => {
+                object.borrow().get("id")
+            }
+            _ => None,
+        };
+        let Some(Value::Int(canvas_id)) = canvas_id else {
+            return Err(ExpectedType {
+                idx,
+                expected: "canvas",
+                got: canvas.typ(),
+            }
+            .into());
+        };
+        let offset_x = option!(args:
+            Int => int { int as f64 },
+            Float => float { float }
+        );
+        let offset_y = option!(args:
+            Int => int { int as f64 },
+            Float => float { float }
+        );
+
+        if u64::try_from(canvas_id).ok() != Some(self.canvas.id()) {
+            return Err("the tilemap was made by another canvas".into());
+        }
+        self.draw(offset_x, offset_y)?;
+        Ok(Value::default())
+    }
+    pub fn draw(&self, offset_x: f64, offset_y: f64) -> Result<(), Box<dyn Error>> {
+        let transform = self.canvas.transform();
+        let (canvas_width, canvas_height) = self.canvas.size()?;
+        let size = f64::from(self.tile_size);
+        // the range of tiles along one axis that overlaps `0..pixels` once transformed
+        let visible = |offset: f64, origin: f64, pixels: u32, tiles: u32| {
+            let first = ((-origin / transform.zoom - offset) / size).floor();
+            let last = ((f64::from(pixels) - origin) / transform.zoom - offset) / size;
+            let clamp = |v: f64| v.clamp(0., tiles.into()) as u32;
+            clamp(first)..clamp(last.ceil())
+        };
+        let columns = visible(offset_x, transform.x, canvas_width, self.width);
+        let rows = visible(offset_y, transform.y, canvas_height, self.height);
+
+        let mut rects = vec![];
+        let mut copies = vec![];
+        for y in rows {
+            for x in columns.clone() {
+                let id = self.tiles[y as usize * self.width as usize + x as usize];
+                if id == 0 {
+                    continue;
+                }
+                // both corners are rounded, so neighboring tiles never leave a gap between them
+                let top_left = transform.point(
+                    offset_x + f64::from(x) * size,
+                    offset_y + f64::from(y) * size,
+                );
+                let bottom_right = transform.point(
+                    offset_x + f64::from(x + 1) * size,
+                    offset_y + f64::from(y + 1) * size,
+                );
+                let dst = Rect::new(
+                    top_left.x(),
+                    top_left.y(),
+                    (bottom_right.x() - top_left.x()).max(0) as u32,
+                    (bottom_right.y() - top_left.y()).max(0) as u32,
+                );
+                match &self.tileset {
+                    Some(tileset) => {
+                        let frame = id - 1;
+                        let src = Rect::new(
+                            ((frame % tileset.columns) * self.tile_size) as i32,
+                            ((frame / tileset.columns) * self.tile_size) as i32,
+                            self.tile_size,
+                            self.tile_size,
+                        );
+                        copies.push((src, dst));
+                    }
+                    None => rects.push(dst),
+                }
+            }
+        }
+        match &self.tileset {
+            Some(tileset) => self.canvas.copy_pixel_rects(tileset.id, &copies)?,
+            None => self.canvas.fill_pixel_rects(&rects)?,
+        }
+        Ok(())
+    }
+}
@@ -1,28 +1,207 @@
 use luna_rs::{
-    function,
     lang::value::{FunctionKind, Object, UserObject, UserObjectError, Value},
     luna_impl::interpreter::Interpreter,
     object, option, set_field, typed, ExpectedType, ExpectedTypes,
 };
 use sdl2::{
-    event::{DisplayEvent, Event, WindowEvent}, mouse::MouseWheelDirection, rect::Rect, render::Canvas, video::{FullscreenType, Orientation, Window}, EventPump, Sdl
+    event::{DisplayEvent, Event, WindowEvent}, mouse::MouseWheelDirection, pixels::PixelFormatEnum, rect::{Point, Rect}, render::{BlendMode, Canvas, Texture}, surface::Surface, video::{FullscreenType, Orientation, Window}, EventPump, Sdl
 };
-use std::{cell::RefCell, collections::HashMap, error::Error, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    error::Error,
+    fmt::Display,
+    path::PathBuf,
+    rc::{Rc, Weak},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::{
+    color, font,
+    geom::geom_module,
+    gui::{gui_module, Input},
+    image::Image,
+    json::json_module,
+    manifest::WindowDefaults,
+    physics::physics_module,
+    replay::{Recorder, Replay},
+    require::{Modules, _require},
+    sandbox::{allowed, fs_module, SandboxError},
+    sprite::SpritesheetObject,
+    storage::{storage_module, Storage},
+    tiled::tiled_module,
+    tilemap::TilemapObject,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// json file of recorded events fed to `events:pull` instead of the real devices
+    pub replay: Option<String>,
+    /// json file every pulled event gets recorded to
+    pub record: Option<String>,
+    /// exposed to the script as the global `args` vector
+    pub args: Vec<String>,
+    pub frames: Rc<Frames>,
+    /// the mouse and keys as of the events pulled so far, for the widgets of `gui`
+    pub input: Rc<Input>,
+    pub modules: Rc<Modules>,
+    /// set while watching a script, so reloads get the same context, windows and events back
+    pub reuse: Option<Rc<Reuse>>,
+    pub window: WindowDefaults,
+    /// directories `require` looks in after the directory of the calling script
+    pub search_paths: Vec<PathBuf>,
+    /// the directory relative image paths are resolved against
+    pub assets: Option<PathBuf>,
+    /// names the directory the `storage` of the script is saved in
+    pub app: Option<String>,
+    /// the only directory the script may read and write files in
+    pub allow_fs: Option<PathBuf>,
+}
+impl Options {
+    /// resolves `path` against the assets directory, if there is one
+    pub fn asset(&self, path: &str) -> Result<PathBuf, SandboxError> {
+        let path = match &self.assets {
+            Some(assets) => assets.join(path),
+            None => PathBuf::from(path),
+        };
+        allowed(self.allow_fs.as_deref(), path)
+    }
+}
+/// frames presented by every canvas, shared with whoever drives the interpreter
+#[derive(Debug, Default)]
+pub struct Frames {
+    pub count: Cell<u64>,
+    /// when set, every canvas copies its pixels into `last` right before presenting
+    pub capture: Cell<bool>,
+    pub last: RefCell<Option<Image>>,
+}
+/// the sdl objects handed out so far, kept alive across reloads of the script
+#[derive(Debug, Default)]
+pub struct Reuse {
+    pub sdl: RefCell<Option<Value>>,
+    /// canvases by their title
+    pub canvases: RefCell<Vec<(String, CanvasObject)>>,
+    pub events: RefCell<Option<Value>>,
+    /// when set, the next `events:pull` returns a `reload` event
+    pub reloaded: Cell<bool>,
+}
+
+/// an error in a method of a user object, like `canvas:rect: expected int/float for argument #2 (y), got string`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MethodError {
+    pub typ: &'static str,
+    pub method: String,
+    pub message: String,
+}
+impl Display for MethodError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.typ, self.method, self.message)
+    }
+}
+impl Error for MethodError {}
+/// names the method and, for type errors, the argument by its position and its name in `params`
+pub fn method_error(
+    typ: &'static str,
+    method: &str,
+    params: &[&str],
+    given: &[Value],
+    err: Box<dyn Error>,
+) -> Box<dyn Error> {
+    let argument = |idx: usize, got: &str| {
+        // `typed!` reports a missing argument with the amount of arguments left, which is always 0
+        let idx = if idx == 0 && got == "null" && given.first().is_some_and(|arg| *arg != Value::Null) {
+            given.len()
+        } else {
+            idx
+        };
+        match params.get(idx) {
+            Some(name) => format!("argument #{} ({name})", idx + 1),
+            None => format!("argument #{}", idx + 1),
+        }
+    };
+    let message = if let Some(err) = err.downcast_ref::<ExpectedType>() {
+        format!("expected {} for {}, got {}", err.expected, argument(err.idx, err.got), err.got)
+    } else if let Some(err) = err.downcast_ref::<ExpectedTypes>() {
+        format!(
+            "expected {} for {}, got {}",
+            err.expected.join("/"),
+            argument(err.idx, err.got),
+            err.got
+        )
+    } else {
+        err.to_string()
+    };
+    Box::new(MethodError {
+        typ,
+        method: method.to_string(),
+        message,
+    })
+}
 
-pub fn insert_module(globals: &mut HashMap<String, Rc<RefCell<Value>>>) {
+pub fn insert_module(globals: &mut HashMap<String, Rc<RefCell<Value>>>, options: &Options) {
+    set_field!(globals."args" = Value::from(options.args.clone()));
+    let modules = Rc::clone(&options.modules);
+    let search_paths = options.search_paths.clone();
+    let root = options.allow_fs.clone();
+    set_field!(globals."require" = Value::Function(FunctionKind::UserFunction(Rc::new(
+        move |interpreter, args| {
+            _require(interpreter, args, &modules, &search_paths, root.as_deref())
+        },
+    ))));
+    let storage = Rc::new(Storage::new(options.app.as_deref()));
+    set_field!(globals."storage" = storage_module(&storage));
+    set_field!(globals."json" = json_module());
+    set_field!(globals."color" = color::color_module());
+    set_field!(globals."geom" = geom_module());
+    set_field!(globals."physics" = physics_module());
+    set_field!(globals."gui" = gui_module(&options.input, &options.frames));
+    set_field!(globals."fs" = fs_module(options.allow_fs.clone()));
+    if options.allow_fs.is_some() {
+        // other programs and other machines aren't bound to the sandbox
+        globals.remove("net");
+        if let Some(Value::Object(os)) = globals.get("os").map(|os| os.borrow().clone()) {
+            os.borrow_mut().fields.remove("exec");
+        }
+        // relative paths have to keep pointing where they did when the sandbox was set up
+        if let Some(Value::Object(env)) = globals.get("env").map(|env| env.borrow().clone()) {
+            env.borrow_mut().fields.remove("set_current_dir");
+        }
+    }
+    let options = Rc::new(options.clone());
+    set_field!(globals."tiled" = tiled_module(&options));
     set_field!(globals."sdl" = object! {
-        "init" = function!(_sdl_init)
+        "init" = Value::Function(FunctionKind::UserFunction(Rc::new(
+            move |interpreter, args| _sdl_init(interpreter, args, &options),
+        )))
     });
 }
 
-pub fn _sdl_init(_: &mut Interpreter, _: Vec<Value>) -> Result<Value, Box<dyn Error>> {
-    Ok(Value::UserObject(Rc::new(RefCell::new(Box::new(
-        SdlObject(sdl2::init()?),
-    )))))
+pub fn _sdl_init(_: &mut Interpreter, _: Vec<Value>, options: &Rc<Options>) -> Result<Value, Box<dyn Error>> {
+    if let Some(sdl) = options.reuse.as_ref().and_then(|reuse| reuse.sdl.borrow().clone()) {
+        return Ok(sdl);
+    }
+    let files = EventFiles {
+        replay: RefCell::new(options.replay.as_deref().map(Replay::load).transpose()?),
+        recorder: RefCell::new(options.record.as_deref().map(Recorder::create).transpose()?),
+    };
+    let sdl = Value::UserObject(Rc::new(RefCell::new(Box::new(
+        SdlObject(sdl2::init()?, Rc::clone(options), Rc::new(files)),
+    ))));
+    if let Some(reuse) = &options.reuse {
+        *reuse.sdl.borrow_mut() = Some(sdl.clone());
+    }
+    Ok(sdl)
+}
+
+/// the replay fed to the event pumps and the recording they write to. they're opened once per
+/// sdl context, so every `sdl:events()` continues where the previous one stopped
+pub struct EventFiles {
+    pub replay: RefCell<Option<Replay>>,
+    pub recorder: RefCell<Option<Recorder>>,
 }
 
 #[derive(Clone)]
-pub struct SdlObject(Sdl);
+pub struct SdlObject(Sdl, Rc<Options>, Rc<EventFiles>);
 impl UserObject for SdlObject {
     fn typ(&self) -> &'static str {
         "sdl"
@@ -39,14 +218,22 @@ impl UserObject for SdlObject {
         }
     }
     fn call(&self, key: &str, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let given = args.clone();
         match key {
             "canvas" => self.call_canvas(args),
             "events" => self.call_events(),
             _ => Err(UserObjectError::CannotCallNull.into()),
         }
+        .map_err(|err| method_error(self.typ(), key, Self::params(key), &given, err))
     }
 }
 impl SdlObject {
+    pub fn params(key: &str) -> &'static [&'static str] {
+        match key {
+            "canvas" => &["title", "width", "height", "options"],
+            _ => &[],
+        }
+    }
     pub fn _canvas(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
         let Some(_self) = args.first().cloned() else {
             return Err(Box::new(UserObjectError::ExpectedSelf("null")));
@@ -61,12 +248,48 @@ impl SdlObject {
     }
     pub fn call_canvas(&self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
         let mut args = args.into_iter().enumerate();
-        let title = typed!(args: String);
-        let width = typed!(args: Int).try_into()?;
-        let height = typed!(args: Int).try_into()?;
+        let defaults = &self.1.window;
+        let title = typed!(args: String?).unwrap_or_else(|| defaults.title.clone());
+        let width = typed!(args: Int?).map(u32::try_from).transpose()?.unwrap_or(defaults.width);
+        let height = typed!(args: Int?).map(u32::try_from).transpose()?.unwrap_or(defaults.height);
         let options = typed!(args: Object?);
 
-        let mut window = self.0.video()?.window(&title, width, height).build()?;
+        if let Some(reuse) = &self.1.reuse {
+            let canvases = reuse.canvases.borrow();
+            if let Some((_, canvas)) = canvases.iter().find(|(other, _)| *other == title) {
+                // the reloaded script starts without the transforms and clipping of the previous one
+                canvas.reset();
+                return Ok(Value::UserObject(Rc::new(RefCell::new(Box::new(canvas.clone())))));
+            }
+        }
+        let canvas = CanvasObject {
+            id: NEXT_CANVAS.fetch_add(1, Ordering::Relaxed),
+            target: Rc::new(RefCell::new(self.canvas_target(&title, width, height, options)?)),
+            transforms: Rc::new(RefCell::new(vec![Transform::default()])),
+            textures: Rc::default(),
+            options: Rc::clone(&self.1),
+        };
+        if let Some(reuse) = &self.1.reuse {
+            reuse.canvases.borrow_mut().push((title, canvas.clone()));
+        }
+        Ok(Value::UserObject(Rc::new(RefCell::new(Box::new(canvas)))))
+    }
+    fn canvas_target(
+        &self,
+        title: &str,
+        width: u32,
+        height: u32,
+        options: Option<Rc<RefCell<Object>>>,
+    ) -> Result<CanvasTarget, Box<dyn Error>> {
+        // the dummy and offscreen drivers (used by `--headless`) have no display to present to,
+        // so draw into a software surface instead
+        let video = self.0.video()?;
+        if matches!(video.current_video_driver(), "dummy" | "offscreen") {
+            let surface = Surface::new(width, height, PixelFormatEnum::RGBA32)?;
+            return Ok(CanvasTarget::Surface(surface.into_canvas()?));
+        }
+
+        let mut window = video.window(title, width, height).build()?;
 
         if let Some(options) = options {
             let options = options.borrow();
@@ -89,9 +312,7 @@ impl SdlObject {
             }
         }
 
-        Ok(Value::UserObject(Rc::new(RefCell::new(Box::new(
-            CanvasObject(window.into_canvas().build()?),
-        )))))
+        Ok(CanvasTarget::Window(window.into_canvas().build()?))
     }
     pub fn _events(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
         let Some(_self) = args.first().cloned() else {
@@ -106,20 +327,98 @@ impl SdlObject {
         }
     }
     pub fn call_events(&self) -> Result<Value, Box<dyn Error>> {
+        if let Some(events) = self.1.reuse.as_ref().and_then(|reuse| reuse.events.borrow().clone()) {
+            return Ok(events);
+        }
         let event_pump = self.0.event_pump()?;
-        Ok(Value::UserObject(Rc::new(RefCell::new(Box::new(
-            EventPumpObject(event_pump),
-        )))))
+        let events = Value::UserObject(Rc::new(RefCell::new(Box::new(
+            EventPumpObject {
+                pump: event_pump,
+                frames: Rc::clone(&self.1.frames),
+                input: Rc::clone(&self.1.input),
+                reuse: self.1.reuse.clone(),
+                files: Rc::clone(&self.2),
+            },
+        ))));
+        if let Some(reuse) = &self.1.reuse {
+            *reuse.events.borrow_mut() = Some(events.clone());
+        }
+        Ok(events)
     }
 }
 
-pub struct CanvasObject(Canvas<Window>);
+pub enum CanvasTarget {
+    Window(Canvas<Window>),
+    Surface(Canvas<Surface<'static>>),
+}
+macro_rules! with_canvas {
+    ($target:expr, $canvas:ident => $body:expr) => {
+        match $target {
+            CanvasTarget::Window($canvas) => $body,
+            CanvasTarget::Surface($canvas) => $body,
+        }
+    };
+}
+/// maps the coordinates given to the drawing methods of a canvas to pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub x: f64,
+    pub y: f64,
+    pub zoom: f64,
+}
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            x: 0.,
+            y: 0.,
+            zoom: 1.,
+        }
+    }
+}
+impl Transform {
+    pub fn point(&self, x: f64, y: f64) -> Point {
+        Point::new(
+            (x * self.zoom + self.x).round() as i32,
+            (y * self.zoom + self.y).round() as i32,
+        )
+    }
+    pub fn rect(&self, x: f64, y: f64, width: f64, height: f64) -> Rect {
+        let origin = self.point(x, y);
+        Rect::new(
+            origin.x(),
+            origin.y(),
+            (width * self.zoom).round() as u32,
+            (height * self.zoom).round() as u32,
+        )
+    }
+}
+
+/// a handle to a canvas, cloned by the objects that draw on it
+#[derive(Clone)]
+pub struct CanvasObject {
+    /// tells the handles to one canvas apart from those to another
+    id: u64,
+    target: Rc<RefCell<CanvasTarget>>,
+    /// the transforms saved by `canvas:push`, the last one is the current one
+    transforms: Rc<RefCell<Vec<Transform>>>,
+    textures: Textures,
+    options: Rc<Options>,
+}
+impl std::fmt::Debug for CanvasObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CanvasObject")
+            .field("id", &self.id)
+            .field("transforms", &self.transforms)
+            .finish_non_exhaustive()
+    }
+}
 impl UserObject for CanvasObject {
     fn typ(&self) -> &'static str {
         "canvas"
     }
     fn get(&self, key: &str) -> Option<Value> {
         match key {
+            "id" => Some(Value::Int(self.id as i64)),
             "present" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
                 Self::_present,
             )))),
@@ -141,23 +440,108 @@ impl UserObject for CanvasObject {
             "rect" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
                 Self::_rect,
             )))),
+            "capture" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_capture,
+            )))),
+            "text" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_text,
+            )))),
+            "texture" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_texture,
+            )))),
+            "draw" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_draw,
+            )))),
+            "spritesheet" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_spritesheet,
+            )))),
+            "draw_ex" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_draw_ex,
+            )))),
+            "push" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_push,
+            )))),
+            "pop" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_pop,
+            )))),
+            "translate" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_translate,
+            )))),
+            "zoom" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_zoom,
+            )))),
+            "clip" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_clip,
+            )))),
+            "viewport" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_viewport,
+            )))),
+            "size" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_size,
+            )))),
+            "logical_size" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_logical_size,
+            )))),
+            "tilemap" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_tilemap,
+            )))),
             _ => None,
         }
     }
     fn call_mut(&mut self, key: &str, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let given = args.clone();
         match key {
             "present" => self.call_present(),
-            "clear" => self.call_clear(),
+            "clear" => self.call_clear(args),
             "color" => self.call_color(args),
             "scale" => self.call_scale(args),
             "line" => self.call_line(args),
             "point" => self.call_point(args),
             "rect" => self.call_rect(args),
+            "capture" => self.call_capture(args),
+            "text" => self.call_text(args),
+            "texture" => self.call_texture(args),
+            "draw" => self.call_draw(args),
+            "spritesheet" => self.call_spritesheet(args),
+            "draw_ex" => self.call_draw_ex(args),
+            "push" => self.call_push(),
+            "pop" => self.call_pop(),
+            "translate" => self.call_translate(args),
+            "zoom" => self.call_zoom(args),
+            "clip" => self.call_clip(args),
+            "viewport" => self.call_viewport(args),
+            "size" => self.call_size(),
+            "logical_size" => self.call_logical_size(args),
+            "tilemap" => self.call_tilemap(args),
             _ => Err(UserObjectError::CannotCallNull.into()),
         }
+        .map_err(|err| method_error(self.typ(), key, Self::params(key), &given, err))
     }
 }
 impl CanvasObject {
+    pub fn params(key: &str) -> &'static [&'static str] {
+        match key {
+            "clear" => &["color"],
+            "color" => &["r", "g", "b", "a"],
+            "scale" => &["scale_x", "scale_y"],
+            "line" => &["start_x", "start_y", "end_x", "end_y"],
+            "point" => &["x", "y"],
+            "rect" => &["x", "y", "width", "height", "fill"],
+            "capture" => &["path"],
+            "text" => &["text", "x", "y", "scale"],
+            "texture" => &["path"],
+            "draw" => &["texture", "x", "y", "width", "height"],
+            "spritesheet" => &["texture", "frame_width", "frame_height"],
+            "draw_ex" => &["texture", "x", "y", "width", "height", "angle", "center_x", "center_y", "flip_h", "flip_v"],
+            "translate" => &["x", "y"],
+            "zoom" => &["zoom"],
+            "clip" => &["x", "y", "width", "height"],
+            "viewport" => &["x", "y", "width", "height"],
+            "logical_size" => &["width", "height", "integer_scale"],
+            "tilemap" => &["width", "height", "tile_size", "tileset"],
+            _ => &[],
+        }
+    }
     pub fn _present(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
         let Some(_self) = args.first().cloned() else {
             return Err(Box::new(UserObjectError::ExpectedSelf("null")));
@@ -171,7 +555,11 @@ impl CanvasObject {
         }
     }
     pub fn call_present(&mut self) -> Result<Value, Box<dyn Error>> {
-        self.0.present();
+        if self.options.frames.capture.get() {
+            *self.options.frames.last.borrow_mut() = Some(self.image()?);
+        }
+        with_canvas!(&mut *self.target.borrow_mut(), canvas => canvas.present());
+        self.options.frames.count.set(self.options.frames.count.get() + 1);
         Ok(Value::default())
     }
     pub fn _clear(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
@@ -186,8 +574,23 @@ impl CanvasObject {
             Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
         }
     }
-    pub fn call_clear(&mut self) -> Result<Value, Box<dyn Error>> {
-        self.0.clear();
+    /// clears with the draw color, or with the given color without changing the draw color
+    pub fn call_clear(&mut self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let color = if args.is_empty() {
+            None
+        } else {
+            Some(color::from_args(args)?)
+        };
+
+        with_canvas!(&mut *self.target.borrow_mut(), canvas => match color {
+            Some(color) => {
+                let draw_color = canvas.draw_color();
+                canvas.set_draw_color(color);
+                canvas.clear();
+                canvas.set_draw_color(draw_color);
+            }
+            None => canvas.clear(),
+        });
         Ok(Value::default())
     }
     pub fn _color(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
@@ -203,13 +606,9 @@ impl CanvasObject {
         }
     }
     pub fn call_color(&mut self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
-        let mut args = args.into_iter().enumerate();
-        let r = typed!(args: Int).clamp(0, 255).try_into()?;
-        let g = typed!(args: Int).clamp(0, 255).try_into()?;
-        let b = typed!(args: Int).clamp(0, 255).try_into()?;
-        let a = typed!(args: Int? int => int.clamp(0, 255).try_into()?);
+        let color = color::from_args(args)?;
 
-        self.0.set_draw_color((r, g, b, a.unwrap_or(255)));
+        with_canvas!(&mut *self.target.borrow_mut(), canvas => canvas.set_draw_color(color));
         Ok(Value::default())
     }
     pub fn _scale(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
@@ -229,7 +628,7 @@ impl CanvasObject {
         let scale_x = typed!(args: Float).clamp(0., f32::MAX.into()) as f32;
         let scale_y = typed!(args: Float).clamp(0., f32::MAX.into()) as f32;
 
-        self.0.set_scale(scale_x, scale_y)?;
+        with_canvas!(&mut *self.target.borrow_mut(), canvas => canvas.set_scale(scale_x, scale_y))?;
         Ok(Value::default())
     }
     pub fn _line(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
@@ -247,22 +646,130 @@ impl CanvasObject {
     pub fn call_line(&mut self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
         let mut args = args.into_iter().enumerate();
         let start_x = option!(args:
-            Int => int {
-                int.clamp(i32::MIN.into(), i32::MAX.into()).try_into()?
-            },
-            Float => float {
-                (float as i64).clamp(i32::MIN.into(), i32::MAX.into()).try_into()?
-            }
+            Int => int { int as f64 },
+            Float => float { float }
         );
         let start_y = option!(args:
-            Int => int {
-                int.clamp(i32::MIN.into(), i32::MAX.into()).try_into()?
-            },
-            Float => float {
-                (float as i64).clamp(i32::MIN.into(), i32::MAX.into()).try_into()?
-            }
+            Int => int { int as f64 },
+            Float => float { float }
         );
         let end_x = option!(args:
+            Int => int { int as f64 },
+            Float => float { float }
+        );
+        let end_y = option!(args:
+            Int => int { int as f64 },
+            Float => float { float }
+        );
+
+        let start = self.transform().point(start_x, start_y);
+        let end = self.transform().point(end_x, end_y);
+        with_canvas!(&mut *self.target.borrow_mut(), canvas => canvas.draw_line(start, end))?;
+        Ok(Value::default())
+    }
+    pub fn _point(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let mut _self = _self.borrow_mut();
+            _self.call_mut("point", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    pub fn call_point(&mut self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let mut args = args.into_iter().enumerate();
+        let x = option!(args:
+            Int => int { int as f64 },
+            Float => float { float }
+        );
+        let y = option!(args:
+            Int => int { int as f64 },
+            Float => float { float }
+        );
+
+        let point = self.transform().point(x, y);
+        with_canvas!(&mut *self.target.borrow_mut(), canvas => canvas.draw_point(point))?;
+        Ok(Value::default())
+    }
+    pub fn _rect(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let mut _self = _self.borrow_mut();
+            _self.call_mut("rect", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    pub fn call_rect(&mut self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let mut args = args.into_iter().enumerate();
+        let x = option!(args:
+            Int => int { int as f64 },
+            Float => float { float }
+        );
+        let y = option!(args:
+            Int => int { int as f64 },
+            Float => float { float }
+        );
+        let width = option!(args:
+            Int => int { int as f64 },
+            Float => float { float }
+        );
+        let height = option!(args:
+            Int => int { int as f64 },
+            Float => float { float }
+        );
+        let fill = typed!(args: Bool?).unwrap_or_default();
+
+        let rect = self.transform().rect(x, y, width, height);
+        if fill {
+            with_canvas!(&mut *self.target.borrow_mut(), canvas => canvas.fill_rect(rect))?;
+        } else {
+            with_canvas!(&mut *self.target.borrow_mut(), canvas => canvas.draw_rect(rect))?;
+        }
+        Ok(Value::default())
+    }
+    pub fn _capture(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let mut _self = _self.borrow_mut();
+            _self.call_mut("capture", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    pub fn call_capture(&mut self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let mut args = args.into_iter().enumerate();
+        let path = typed!(args: String);
+        let path = allowed(self.options.allow_fs.as_deref(), path)?;
+
+        self.image()?.save(path)?;
+        Ok(Value::default())
+    }
+    pub fn _text(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let mut _self = _self.borrow_mut();
+            _self.call_mut("text", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    pub fn call_text(&mut self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let mut args = args.into_iter().enumerate();
+        let text = typed!(args: String);
+        let x = option!(args:
             Int => int {
                 int.clamp(i32::MIN.into(), i32::MAX.into()).try_into()?
             },
@@ -270,7 +777,7 @@ impl CanvasObject {
                 (float as i64).clamp(i32::MIN.into(), i32::MAX.into()).try_into()?
             }
         );
-        let end_y = option!(args:
+        let y = option!(args:
             Int => int {
                 int.clamp(i32::MIN.into(), i32::MAX.into()).try_into()?
             },
@@ -278,24 +785,81 @@ impl CanvasObject {
                 (float as i64).clamp(i32::MIN.into(), i32::MAX.into()).try_into()?
             }
         );
+        let scale = typed!(args: Int? int => int.clamp(1, 64) as i32).unwrap_or(1);
 
-        self.0.draw_line((start_x, start_y), (end_x, end_y))?;
-        Ok(Value::default())
+        // the text is laid out where the script sees it and then moved like every other shape
+        let transform = self.transform();
+        let (width, _) = self.size()?;
+        let max_x = ((f64::from(width) - transform.x) / transform.zoom) as i32;
+        let (rects, end_y) = font::layout(&text, x, y, scale, max_x);
+        let rects = rects
+            .iter()
+            .map(|rect| {
+                transform.rect(
+                    rect.x().into(),
+                    rect.y().into(),
+                    rect.width().into(),
+                    rect.height().into(),
+                )
+            })
+            .collect::<Vec<_>>();
+        with_canvas!(&mut *self.target.borrow_mut(), canvas => canvas.fill_rects(&rects))?;
+        Ok(Value::Int(end_y.into()))
     }
-    pub fn _point(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+    pub fn _texture(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
         let Some(_self) = args.first().cloned() else {
             return Err(Box::new(UserObjectError::ExpectedSelf("null")));
         };
         args.remove(0);
         if let Value::UserObject(_self) = _self {
             let mut _self = _self.borrow_mut();
-            _self.call_mut("point", args)
+            _self.call_mut("texture", args)
         } else {
             Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
         }
     }
-    pub fn call_point(&mut self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+    pub fn call_texture(&mut self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let mut args = args.into_iter().enumerate();
+        let path = typed!(args: String);
+
+        let path = self.options.asset(&path)?;
+        let image = Image::load_png(&path)
+            .map_err(|err| format!("couldn't load {}: {err}", path.display()))?;
+        let mut texture = with_canvas!(&*self.target.borrow(), canvas => canvas
+            .texture_creator()
+            .create_texture_static(PixelFormatEnum::RGBA32, image.width, image.height))?;
+        texture.update(None, &image.pixels, image.width as usize * 4)?;
+        texture.set_blend_mode(BlendMode::Blend);
+
+        let id = NEXT_TEXTURE.fetch_add(1, Ordering::Relaxed);
+        self.textures.borrow_mut().insert(id, texture);
+        Ok(Value::UserObject(Rc::new(RefCell::new(Box::new(TextureObject {
+            id,
+            width: image.width,
+            height: image.height,
+            textures: Rc::downgrade(&self.textures),
+        })))))
+    }
+    pub fn _draw(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let mut _self = _self.borrow_mut();
+            _self.call_mut("draw", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    pub fn call_draw(&mut self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
         let mut args = args.into_iter().enumerate();
+        let (idx, texture) = args.next().unwrap_or_default();
+        let id = texture_id(&texture).ok_or(ExpectedType {
+            idx,
+            expected: "texture",
+            got: texture.typ(),
+        })?;
         let x = option!(args:
             Int => int {
                 int.clamp(i32::MIN.into(), i32::MAX.into()).try_into()?
@@ -312,24 +876,65 @@ impl CanvasObject {
                 (float as i64).clamp(i32::MIN.into(), i32::MAX.into()).try_into()?
             }
         );
+        let width = typed!(args: Int? int => int.clamp(u32::MIN.into(), u32::MAX.into()).try_into()?);
+        let height = typed!(args: Int? int => int.clamp(u32::MIN.into(), u32::MAX.into()).try_into()?);
 
-        self.0.draw_point((x, y))?;
+        let (texture_width, texture_height) = self.texture_size(id)?;
+        let dst = Rect::new(
+            x,
+            y,
+            width.unwrap_or(texture_width),
+            height.unwrap_or(texture_height),
+        );
+        self.copy(id, None, dst, (0., None), (false, false))?;
         Ok(Value::default())
     }
-    pub fn _rect(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+    pub fn _spritesheet(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
         let Some(_self) = args.first().cloned() else {
             return Err(Box::new(UserObjectError::ExpectedSelf("null")));
         };
         args.remove(0);
         if let Value::UserObject(_self) = _self {
             let mut _self = _self.borrow_mut();
-            _self.call_mut("rect", args)
+            _self.call_mut("spritesheet", args)
         } else {
             Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
         }
     }
-    pub fn call_rect(&mut self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+    pub fn call_spritesheet(&mut self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
         let mut args = args.into_iter().enumerate();
+        let (idx, texture) = args.next().unwrap_or_default();
+        let id = texture_id(&texture).ok_or(ExpectedType {
+            idx,
+            expected: "texture",
+            got: texture.typ(),
+        })?;
+        let frame_width = typed!(args: Int).clamp(1, u32::MAX.into()).try_into()?;
+        let frame_height = typed!(args: Int).clamp(1, u32::MAX.into()).try_into()?;
+
+        let sheet = SpritesheetObject::new(self.clone(), texture, id, frame_width, frame_height)?;
+        Ok(Value::UserObject(Rc::new(RefCell::new(Box::new(sheet)))))
+    }
+    pub fn _draw_ex(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let mut _self = _self.borrow_mut();
+            _self.call_mut("draw_ex", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    pub fn call_draw_ex(&mut self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let mut args = args.into_iter().enumerate();
+        let (idx, texture) = args.next().unwrap_or_default();
+        let id = texture_id(&texture).ok_or(ExpectedType {
+            idx,
+            expected: "texture",
+            got: texture.typ(),
+        })?;
         let x = option!(args:
             Int => int {
                 int.clamp(i32::MIN.into(), i32::MAX.into()).try_into()?
@@ -362,18 +967,498 @@ impl CanvasObject {
                 (float as i64).clamp(u32::MIN.into(), u32::MAX.into()).try_into()?
             }
         );
-        let fill = typed!(args: Bool?).unwrap_or_default();
+        let angle = option!(args:
+            Int => int { int as f64 },
+            Float => float { float }
+        );
+        let mut center = || -> Result<Option<i32>, Box<dyn Error>> {
+            Ok(match args.next() {
+                Some((_, Value::Int(int))) => Some(int.clamp(i32::MIN.into(), i32::MAX.into()) as i32),
+                Some((_, Value::Float(float))) => Some(float as i32),
+                None | Some((_, Value::Null)) => None,
+                Some((idx, arg)) => {
+                    return Err(ExpectedTypes {
+                        idx,
+                        expected: vec!["int", "float"],
+                        got: arg.typ(),
+                    }
+                    .into())
+                }
+            })
+        };
+        let center_x = center()?;
+        let center_y = center()?;
+        let flip_h = typed!(args: Bool?).unwrap_or_default();
+        let flip_v = typed!(args: Bool?).unwrap_or_default();
 
-        if fill {
-            self.0.fill_rect(Rect::new(x, y, width, height))?;
+        // rotating around the center of the destination is sdl's default
+        let center = match (center_x, center_y) {
+            (None, None) => None,
+            (x, y) => Some(Point::new(
+                x.unwrap_or((width / 2) as i32),
+                y.unwrap_or((height / 2) as i32),
+            )),
+        };
+        let dst = Rect::new(x, y, width, height);
+        self.copy(id, None, dst, (angle, center), (flip_h, flip_v))?;
+        Ok(Value::default())
+    }
+    pub fn _push(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let mut _self = _self.borrow_mut();
+            _self.call_mut("push", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    pub fn call_push(&mut self) -> Result<Value, Box<dyn Error>> {
+        let transform = self.transform();
+        self.transforms.borrow_mut().push(transform);
+        Ok(Value::default())
+    }
+    pub fn _pop(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let mut _self = _self.borrow_mut();
+            _self.call_mut("pop", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    pub fn call_pop(&mut self) -> Result<Value, Box<dyn Error>> {
+        let mut transforms = self.transforms.borrow_mut();
+        if transforms.len() <= 1 {
+            return Err("there is no transform left to pop, every pop needs a push".into());
+        }
+        transforms.pop();
+        Ok(Value::default())
+    }
+    pub fn _translate(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let mut _self = _self.borrow_mut();
+            _self.call_mut("translate", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    /// moves the origin by `(x, y)`, measured in the current zoom
+    pub fn call_translate(&mut self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let mut args = args.into_iter().enumerate();
+        let x = option!(args:
+            Int => int { int as f64 },
+            Float => float { float }
+        );
+        let y = option!(args:
+            Int => int { int as f64 },
+            Float => float { float }
+        );
+
+        if let Some(transform) = self.transforms.borrow_mut().last_mut() {
+            transform.x += x * transform.zoom;
+            transform.y += y * transform.zoom;
+        }
+        Ok(Value::default())
+    }
+    pub fn _zoom(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let mut _self = _self.borrow_mut();
+            _self.call_mut("zoom", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    /// scales everything drawn from here on around the current origin
+    pub fn call_zoom(&mut self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let mut args = args.into_iter().enumerate();
+        let zoom = option!(args:
+            Int => int { int as f64 },
+            Float => float { float }
+        );
+
+        if !(zoom > 0. && zoom.is_finite()) {
+            return Err(format!("the zoom has to be positive, got {zoom}").into());
+        }
+        if let Some(transform) = self.transforms.borrow_mut().last_mut() {
+            transform.zoom *= zoom;
+        }
+        Ok(Value::default())
+    }
+    pub fn _clip(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let mut _self = _self.borrow_mut();
+            _self.call_mut("clip", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    /// limits drawing to a rect in pixels, or lifts the limit when called without one
+    pub fn call_clip(&mut self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let rect = pixel_rect(args)?;
+
+        with_canvas!(&mut *self.target.borrow_mut(), canvas => canvas.set_clip_rect(rect));
+        Ok(Value::default())
+    }
+    pub fn _viewport(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let mut _self = _self.borrow_mut();
+            _self.call_mut("viewport", args)
         } else {
-            self.0.draw_rect(Rect::new(x, y, width, height))?;
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
         }
+    }
+    /// moves the origin to the top left of a rect in pixels and limits drawing to it,
+    /// or goes back to the whole canvas when called without one
+    pub fn call_viewport(&mut self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let rect = pixel_rect(args)?;
+
+        with_canvas!(&mut *self.target.borrow_mut(), canvas => canvas.set_viewport(rect));
         Ok(Value::default())
     }
+    pub fn _size(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let mut _self = _self.borrow_mut();
+            _self.call_mut("size", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    pub fn call_size(&mut self) -> Result<Value, Box<dyn Error>> {
+        let (width, height) = self.size()?;
+        Ok(object! {
+            "width" = width,
+            "height" = height
+        })
+    }
+    pub fn _logical_size(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let mut _self = _self.borrow_mut();
+            _self.call_mut("logical_size", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    /// lets the script draw in a resolution of its own, which sdl scales to fit the output and
+    /// letterboxes. this replaces `canvas:scale`, and sdl maps the coordinates of mouse events
+    /// to it as well. called without a size, drawing goes back to pixels
+    pub fn call_logical_size(&mut self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let mut args = args.into_iter().enumerate();
+        let width = typed!(args: Int?);
+        let height = typed!(args: Int?);
+        let integer_scale = typed!(args: Bool?).unwrap_or_default();
+
+        let (width, height) = match (width, height) {
+            (Some(width), Some(height)) => (
+                width.clamp(1, i32::MAX.into()).try_into()?,
+                height.clamp(1, i32::MAX.into()).try_into()?,
+            ),
+            (None, None) => (0, 0),
+            (_, None) => {
+                return Err(ExpectedType {
+                    idx: 1,
+                    expected: "int",
+                    got: "null",
+                }
+                .into())
+            }
+            (None, _) => {
+                return Err(ExpectedType {
+                    idx: 0,
+                    expected: "int",
+                    got: "null",
+                }
+                .into())
+            }
+        };
+        with_canvas!(&mut *self.target.borrow_mut(), canvas => {
+            canvas.set_logical_size(width, height)?;
+            canvas.set_integer_scale(integer_scale)?;
+        });
+        Ok(Value::default())
+    }
+    pub fn _tilemap(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let mut _self = _self.borrow_mut();
+            _self.call_mut("tilemap", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    /// a grid of `width` by `height` tiles of `tile_size` pixels, drawn from `tileset` if given
+    pub fn call_tilemap(&mut self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let mut args = args.into_iter().enumerate();
+        let width = typed!(args: Int).clamp(1, u32::MAX.into()).try_into()?;
+        let height = typed!(args: Int).clamp(1, u32::MAX.into()).try_into()?;
+        let tile_size = typed!(args: Int).clamp(1, u32::MAX.into()).try_into()?;
+        let tileset = match args.next() {
+            None | Some((_, Value::Null)) => None,
+            Some((idx, texture)) => {
+                let id = texture_id(&texture).ok_or(ExpectedType {
+                    idx,
+                    expected: "texture",
+                    got: texture.typ(),
+                })?;
+                Some((texture, id))
+            }
+        };
+
+        let tilemap = TilemapObject::new(self.clone(), width, height, tile_size, tileset)?;
+        Ok(Value::UserObject(Rc::new(RefCell::new(Box::new(tilemap)))))
+    }
+    /// draws the `src` part of a texture of this canvas, rotated by `angle` degrees around `center`
+    /// (relative to `dst`, by default its center) and flipped horizontally and vertically
+    pub fn copy(
+        &self,
+        id: u64,
+        src: Option<Rect>,
+        dst: Rect,
+        (angle, center): (f64, Option<Point>),
+        (flip_h, flip_v): (bool, bool),
+    ) -> Result<(), String> {
+        let textures = self.textures.borrow();
+        let texture = textures
+            .get(&id)
+            .ok_or("the texture was loaded by another canvas")?;
+        let transform = self.transform();
+        let dst = transform.rect(
+            dst.x().into(),
+            dst.y().into(),
+            dst.width().into(),
+            dst.height().into(),
+        );
+        // the center is relative to `dst`, so it's only zoomed
+        let center = center.map(|center| {
+            let zoom = transform.zoom;
+            Point::new(
+                (f64::from(center.x()) * zoom).round() as i32,
+                (f64::from(center.y()) * zoom).round() as i32,
+            )
+        });
+        with_canvas!(&mut *self.target.borrow_mut(), canvas => {
+            canvas.copy_ex(texture, src, dst, angle, center, flip_h, flip_v)
+        })
+    }
+    /// fills rects that are already in pixels, so the transform doesn't apply
+    pub fn fill_pixel_rects(&self, rects: &[Rect]) -> Result<(), String> {
+        with_canvas!(&mut *self.target.borrow_mut(), canvas => canvas.fill_rects(rects))
+    }
+    /// draws the `src` part of a texture into each `dst`, which are already in pixels
+    pub fn copy_pixel_rects(&self, id: u64, rects: &[(Rect, Rect)]) -> Result<(), String> {
+        let textures = self.textures.borrow();
+        let texture = textures
+            .get(&id)
+            .ok_or("the texture was loaded by another canvas")?;
+        with_canvas!(&mut *self.target.borrow_mut(), canvas => {
+            for (src, dst) in rects {
+                canvas.copy(texture, *src, *dst)?;
+            }
+        });
+        Ok(())
+    }
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+    /// drops every transform, the clip rect and the viewport
+    pub fn reset(&self) {
+        *self.transforms.borrow_mut() = vec![Transform::default()];
+        with_canvas!(&mut *self.target.borrow_mut(), canvas => {
+            canvas.set_clip_rect(None);
+            canvas.set_viewport(None);
+        });
+    }
+    pub fn transform(&self) -> Transform {
+        self.transforms.borrow().last().copied().unwrap_or_default()
+    }
+    pub fn texture_size(&self, id: u64) -> Result<(u32, u32), String> {
+        let textures = self.textures.borrow();
+        let texture = textures
+            .get(&id)
+            .ok_or("the texture was loaded by another canvas")?;
+        let query = texture.query();
+        Ok((query.width, query.height))
+    }
+    /// the size scripts draw in, which is the logical size if one is set
+    pub fn size(&self) -> Result<(u32, u32), String> {
+        with_canvas!(&*self.target.borrow(), canvas => match canvas.logical_size() {
+            (0, 0) => canvas.output_size(),
+            size => Ok(size),
+        })
+    }
+    pub fn image(&self) -> Result<Image, String> {
+        let (width, height) = with_canvas!(&*self.target.borrow(), canvas => canvas.output_size())?;
+        let pixels = with_canvas!(&mut *self.target.borrow_mut(), canvas => {
+            // sdl reads the viewport, which logical sizes and `canvas:viewport` move and scale
+            let logical_size = canvas.logical_size();
+            let (scale_x, scale_y) = canvas.scale();
+            let viewport = canvas.viewport();
+            canvas.set_logical_size(0, 0).map_err(|err| err.to_string())?;
+            canvas.set_scale(1., 1.)?;
+            canvas.set_viewport(None);
+            let pixels = canvas.read_pixels(None, PixelFormatEnum::RGBA32);
+            canvas.set_logical_size(logical_size.0, logical_size.1).map_err(|err| err.to_string())?;
+            canvas.set_scale(scale_x, scale_y)?;
+            canvas.set_viewport(viewport);
+            pixels
+        })?;
+        Ok(Image {
+            width,
+            height,
+            pixels,
+        })
+    }
+}
+
+/// the textures of a canvas by their id. the canvas owns them instead of their `texture` objects,
+/// so a texture can't outlive the renderer it was created with
+pub type Textures = Rc<RefCell<HashMap<u64, Texture>>>;
+static NEXT_TEXTURE: AtomicU64 = AtomicU64::new(0);
+static NEXT_CANVAS: AtomicU64 = AtomicU64::new(0);
+
+/// the optional `x, y, width, height` arguments of `canvas:clip` and `canvas:viewport`
+fn pixel_rect(args: Vec<Value>) -> Result<Option<Rect>, Box<dyn Error>> {
+    if matches!(args.first(), None | Some(Value::Null)) {
+        return Ok(None);
+    }
+    let mut args = args.into_iter().enumerate();
+    let x = typed!(args: Int).clamp(i32::MIN.into(), i32::MAX.into()).try_into()?;
+    let y = typed!(args: Int).clamp(i32::MIN.into(), i32::MAX.into()).try_into()?;
+    let width = typed!(args: Int).clamp(u32::MIN.into(), u32::MAX.into()).try_into()?;
+    let height = typed!(args: Int).clamp(u32::MIN.into(), u32::MAX.into()).try_into()?;
+    Ok(Some(Rect::new(x, y, width, height)))
 }
 
-pub struct EventPumpObject(EventPump);
+/// a handle to a texture of a canvas, which frees the texture once the script drops it
+pub struct TextureObject {
+    id: u64,
+    width: u32,
+    height: u32,
+    textures: Weak<RefCell<HashMap<u64, Texture>>>,
+}
+impl UserObject for TextureObject {
+    fn typ(&self) -> &'static str {
+        "texture"
+    }
+    fn get(&self, key: &str) -> Option<Value> {
+        match key {
+            "id" => Some(Value::Int(self.id as i64)),
+            "width" => Some(Value::Int(self.width.into())),
+            "height" => Some(Value::Int(self.height.into())),
+            "tint" => Some(Value::Function(FunctionKind::UserFunction(Rc::new(
+                Self::_tint,
+            )))),
+            _ => None,
+        }
+    }
+    fn call_mut(&mut self, key: &str, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let given = args.clone();
+        match key {
+            "tint" => self.call_tint(args),
+            _ => Err(UserObjectError::CannotCallNull.into()),
+        }
+        .map_err(|err| method_error(self.typ(), key, Self::params(key), &given, err))
+    }
+}
+impl TextureObject {
+    pub fn params(key: &str) -> &'static [&'static str] {
+        match key {
+            "tint" => &["r", "g", "b", "a"],
+            _ => &[],
+        }
+    }
+    pub fn _tint(_: &mut Interpreter, mut args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let Some(_self) = args.first().cloned() else {
+            return Err(Box::new(UserObjectError::ExpectedSelf("null")));
+        };
+        args.remove(0);
+        if let Value::UserObject(_self) = _self {
+            let mut _self = _self.borrow_mut();
+            _self.call_mut("tint", args)
+        } else {
+            Err(Box::new(UserObjectError::ExpectedSelf(_self.typ())))
+        }
+    }
+    /// multiplies the colors of the texture by `(r, g, b)` and its alpha by `a` when drawn
+    pub fn call_tint(&mut self, args: Vec<Value>) -> Result<Value, Box<dyn Error>> {
+        let color = color::from_args(args)?;
+
+        let textures = self.textures.upgrade().ok_or("the canvas of the texture is gone")?;
+        let mut textures = textures.borrow_mut();
+        if let Some(texture) = textures.get_mut(&self.id) {
+            texture.set_color_mod(color.r, color.g, color.b);
+            texture.set_alpha_mod(color.a);
+        }
+        Ok(Value::default())
+    }
+}
+impl Drop for TextureObject {
+    fn drop(&mut self) {
+        // once the canvas is gone, its renderer already freed the texture
+        let Some(textures) = self.textures.upgrade() else {
+            return;
+        };
+        let texture = textures.borrow_mut().remove(&self.id);
+        if let Some(texture) = texture {
+            // SAFETY: the canvas, and with it the renderer, is still alive
+            unsafe { texture.destroy() };
+        }
+    }
+}
+/// the id of the `texture` object in `value`, for looking it up in the textures of a canvas
+pub fn texture_id(value: &Value) -> Option<u64> {
+    let Value::UserObject(object) = value else {
+        return None;
+    };
+    let object = object.borrow();
+    if object.typ() != "texture" {
+        return None;
+    }
+    match object.get("id") {
+        Some(Value::Int(id)) => id.try_into().ok(),
+        _ => None,
+    }
+}
+
+pub struct EventPumpObject {
+    pump: EventPump,
+    frames: Rc<Frames>,
+    input: Rc<Input>,
+    reuse: Option<Rc<Reuse>>,
+    files: Rc<EventFiles>,
+}
 impl UserObject for EventPumpObject {
     fn typ(&self) -> &'static str {
         "event-pump"
@@ -389,8 +1474,11 @@ impl UserObject for EventPumpObject {
     fn call_mut(&mut self, key: &str, _: Vec<Value>) -> Result<Value, Box<dyn Error>> {
         match key {
             "pull" => self.call_pull(),
+            // not a field, since scripts pull their events
+            "idle" => self.call_idle(),
             _ => Err(UserObjectError::CannotCallNull.into()),
         }
+        .map_err(|err| method_error(self.typ(), key, &[], &[], err))
     }
 }
 impl EventPumpObject {
@@ -407,414 +1495,442 @@ impl EventPumpObject {
         }
     }
     pub fn call_pull(&mut self) -> Result<Value, Box<dyn Error>> {
-        let event = self.0.poll_event();
-        if let Some(event) = event {
-            Ok(match event {
-                Event::Quit { timestamp } => object! {
-                    "kind" = "quit",
-                    "timestamp" = timestamp
-                },
-                Event::AppTerminating { timestamp } => object! {
-                    "kind" = "app_terminated",
-                    "timestamp" = timestamp
-                },
-                Event::AppLowMemory { timestamp } => object! {
-                    "kind" = "app_low_memory",
-                    "timestamp" = timestamp
-                },
-                Event::AppWillEnterBackground { timestamp } => object! {
-                    "kind" = "app_will_enter_background",
-                    "timestamp" = timestamp
-                },
-                Event::AppDidEnterBackground { timestamp } => object! {
-                    "kind" = "app_did_enter_background",
-                    "timestamp" = timestamp
-                },
-                Event::AppWillEnterForeground { timestamp } => object! {
-                    "kind" = "app_will_enter_foreground",
-                    "timestamp" = timestamp
-                },
-                Event::AppDidEnterForeground { timestamp } => object! {
-                    "kind" = "app_did_enter_foreground",
-                    "timestamp" = timestamp
-                },
-                Event::Display {
-                    timestamp,
-                    display_index,
-                    display_event,
-                } => object! {
-                    "kind" = "display",
-                    "display_index" = display_index,
-                    "display_event" = match display_event {
-                        DisplayEvent::None => "none",
-                        DisplayEvent::Connected => "connected",
-                        DisplayEvent::Disconnected => "disconnected",
-                        DisplayEvent::Orientation(orientation) => match orientation {
-                            Orientation::Unknown => "unknown",
-                            Orientation::Landscape => "landscape",
-                            Orientation::LandscapeFlipped => "landscape_flipped",
-                            Orientation::Portrait => "portrait",
-                            Orientation::PortraitFlipped => "portrait_flipped",
-                        },
-                    },
-                    "timestamp" = timestamp
-                },
-                Event::Window {
-                    timestamp,
-                    window_id,
-                    win_event,
-                } => object! {
-                    "kind" = "window",
-                    "window_id" = window_id,
-                    "win_event" = match win_event {
-                        WindowEvent::None => "none",
-                        WindowEvent::Close => "close",
-                        WindowEvent::Leave => "leave",
-                        WindowEvent::Maximized => "maximized",
-                        WindowEvent::Resized(_, _) => "resized",
-                        WindowEvent::Hidden => "hidden",
-                        WindowEvent::HitTest => "hit_test",
-                        WindowEvent::FocusLost => "focus_lost",
-                        WindowEvent::Enter => "enter",
-                        WindowEvent::Minimized => "minimized",
-                        WindowEvent::Moved(_, _) => "moved",
-                        WindowEvent::Shown => "shown",
-                        WindowEvent::DisplayChanged(_) => "display_changed",
-                        WindowEvent::TakeFocus => "take_focus",
-                        WindowEvent::FocusGained => "focus_gained",
-                        WindowEvent::Restored => "restored",
-                        WindowEvent::SizeChanged(_, _) => "size_changed",
-                        WindowEvent::Exposed => "exposed",
-                        WindowEvent::ICCProfChanged => "icc_prof_changed",
-                    },
-                    "width" = if let WindowEvent::Resized(width, _) | WindowEvent::SizeChanged(width, _) | WindowEvent::Moved(width, _) = win_event {
-                        Value::Int(width as i64)
-                    } else {
-                        Value::default()
-                    },
-                    "height" = if let WindowEvent::Resized(_, height) | WindowEvent::SizeChanged(_, height) | WindowEvent::Moved(_, height) = win_event {
-                        Value::Int(height as i64)
-                    } else {
-                        Value::default()
-                    },
-                    "timestamp" = timestamp
-                },
-                Event::KeyDown {
-                    timestamp,
-                    window_id,
-                    keycode,
-                    scancode,
-                    keymod,
-                    repeat,
-                } => object! {
-                    "kind" = "key_down",
-                    "window_id" = window_id,
-                    "keycode" = keycode.map(|code| Value::String(code.to_string().to_lowercase())).unwrap_or_default(),
-                    "scancode" = scancode.map(|code| Value::String(code.to_string().to_lowercase())).unwrap_or_default(),
-                    "keymod" = keymod.to_string().to_lowercase(),
-                    "repeat" = repeat,
-                    "timestamp" = timestamp
-                },
-                Event::KeyUp {
-                    timestamp,
-                    window_id,
-                    keycode,
-                    scancode,
-                    keymod,
-                    repeat,
-                } => object! {
-                    "kind" = "key_up",
-                    "window_id" = window_id,
-                    "keycode" = keycode.map(|code| Value::String(code.to_string().to_lowercase())).unwrap_or_default(),
-                    "scancode" = scancode.map(|code| Value::String(code.to_string().to_lowercase())).unwrap_or_default(),
-                    "keymod" = keymod.to_string().to_lowercase(),
-                    "repeat" = repeat,
-                    "timestamp" = timestamp
-                },
-                Event::TextEditing {
-                    timestamp,
-                    window_id,
-                    text,
-                    start,
-                    length,
-                } => object! {
-                    "kind" = "text_editing",
-                    "window_id" = window_id,
-                    "text" = text,
-                    "start" = start,
-                    "length" = length,
-                    "timestamp" = timestamp
-                },
-                Event::TextInput {
-                    timestamp,
-                    window_id,
-                    text,
-                } => object! {
-                    "kind" = "text_input",
-                    "window_id" = window_id,
-                    "text" = text,
-                    "timestamp" = timestamp
-                },
-                Event::MouseMotion {
-                    timestamp,
-                    window_id,
-                    which,
-                    mousestate: _,
-                    x,
-                    y,
-                    xrel,
-                    yrel,
-                } => object! {
-                    "kind" = "mouse_motion",
-                    "window_id" = window_id,
-                    "which" = which,
-                    "x" = x,
-                    "y" = y,
-                    "xrel" = xrel,
-                    "yrel" = yrel,
-                    "timestamp" = timestamp
-                },
-                Event::MouseButtonDown {
-                    timestamp,
-                    window_id,
-                    which,
-                    mouse_btn,
-                    clicks,
-                    x,
-                    y,
-                } => object! {
-                    "kind" = "mouse_button_down",
-                    "window_id" = window_id,
-                    "which" = which,
-                    "mouse_btn" = mouse_btn as u8,
-                    "clicks" = clicks,
-                    "x" = x,
-                    "y" = y,
-                    "timestamp" = timestamp
-                },
-                Event::MouseButtonUp {
-                    timestamp,
-                    window_id,
-                    which,
-                    mouse_btn,
-                    clicks,
-                    x,
-                    y,
-                } => object! {
-                    "kind" = "mouse_button_up",
-                    "window_id" = window_id,
-                    "which" = which,
-                    "mouse_btn" = mouse_btn as u8,
-                    "clicks" = clicks,
-                    "x" = x,
-                    "y" = y,
-                    "timestamp" = timestamp
-                },
-                Event::MouseWheel {
-                    timestamp,
-                    window_id,
-                    which,
-                    x,
-                    y,
-                    direction,
-                    precise_x,
-                    precise_y,
-                } => object! {
-                    "kind" = "mouse_wheel",
-                    "window_id" = window_id,
-                    "which" = which,
-                    "x" = x,
-                    "y" = y,
-                    "direction" = match direction {
-                        MouseWheelDirection::Normal => 0,
-                        MouseWheelDirection::Flipped => 1,
-                        MouseWheelDirection::Unknown(v) => v,
-                    },
-                    "precise_x" = precise_x,
-                    "precise_y" = precise_y,
-                    "timestamp" = timestamp
-                },
-                // Event::JoyAxisMotion {
-                //     timestamp,
-                //     which,
-                //     axis_idx,
-                //     value,
-                // } => todo!(),
-                // Event::JoyBallMotion {
-                //     timestamp,
-                //     which,
-                //     ball_idx,
-                //     xrel,
-                //     yrel,
-                // } => todo!(),
-                // Event::JoyHatMotion {
-                //     timestamp,
-                //     which,
-                //     hat_idx,
-                //     state,
-                // } => todo!(),
-                // Event::JoyButtonDown {
-                //     timestamp,
-                //     which,
-                //     button_idx,
-                // } => todo!(),
-                // Event::JoyButtonUp {
-                //     timestamp,
-                //     which,
-                //     button_idx,
-                // } => todo!(),
-                // Event::JoyDeviceAdded { timestamp, which } => todo!(),
-                // Event::JoyDeviceRemoved { timestamp, which } => todo!(),
-                // Event::ControllerAxisMotion {
-                //     timestamp,
-                //     which,
-                //     axis,
-                //     value,
-                // } => todo!(),
-                // Event::ControllerButtonDown {
-                //     timestamp,
-                //     which,
-                //     button,
-                // } => todo!(),
-                // Event::ControllerButtonUp {
-                //     timestamp,
-                //     which,
-                //     button,
-                // } => todo!(),
-                // Event::ControllerDeviceAdded { timestamp, which } => todo!(),
-                // Event::ControllerDeviceRemoved { timestamp, which } => todo!(),
-                // Event::ControllerDeviceRemapped { timestamp, which } => todo!(),
-                // Event::ControllerTouchpadDown {
-                //     timestamp,
-                //     which,
-                //     touchpad,
-                //     finger,
-                //     x,
-                //     y,
-                //     pressure,
-                // } => todo!(),
-                // Event::ControllerTouchpadMotion {
-                //     timestamp,
-                //     which,
-                //     touchpad,
-                //     finger,
-                //     x,
-                //     y,
-                //     pressure,
-                // } => todo!(),
-                // Event::ControllerTouchpadUp {
-                //     timestamp,
-                //     which,
-                //     touchpad,
-                //     finger,
-                //     x,
-                //     y,
-                //     pressure,
-                // } => todo!(),
-                // Event::FingerDown {
-                //     timestamp,
-                //     touch_id,
-                //     finger_id,
-                //     x,
-                //     y,
-                //     dx,
-                //     dy,
-                //     pressure,
-                // } => todo!(),
-                // Event::FingerUp {
-                //     timestamp,
-                //     touch_id,
-                //     finger_id,
-                //     x,
-                //     y,
-                //     dx,
-                //     dy,
-                //     pressure,
-                // } => todo!(),
-                // Event::FingerMotion {
-                //     timestamp,
-                //     touch_id,
-                //     finger_id,
-                //     x,
-                //     y,
-                //     dx,
-                //     dy,
-                //     pressure,
-                // } => todo!(),
-                // Event::DollarGesture {
-                //     timestamp,
-                //     touch_id,
-                //     gesture_id,
-                //     num_fingers,
-                //     error,
-                //     x,
-                //     y,
-                // } => todo!(),
-                // Event::DollarRecord {
-                //     timestamp,
-                //     touch_id,
-                //     gesture_id,
-                //     num_fingers,
-                //     error,
-                //     x,
-                //     y,
-                // } => todo!(),
-                // Event::MultiGesture {
-                //     timestamp,
-                //     touch_id,
-                //     d_theta,
-                //     d_dist,
-                //     x,
-                //     y,
-                //     num_fingers,
-                // } => todo!(),
-                // Event::ClipboardUpdate { timestamp } => todo!(),
-                // Event::DropFile {
-                //     timestamp,
-                //     window_id,
-                //     filename,
-                // } => todo!(),
-                // Event::DropText {
-                //     timestamp,
-                //     window_id,
-                //     filename,
-                // } => todo!(),
-                // Event::DropBegin {
-                //     timestamp,
-                //     window_id,
-                // } => todo!(),
-                // Event::DropComplete {
-                //     timestamp,
-                //     window_id,
-                // } => todo!(),
-                // Event::AudioDeviceAdded {
-                //     timestamp,
-                //     which,
-                //     iscapture,
-                // } => todo!(),
-                // Event::AudioDeviceRemoved {
-                //     timestamp,
-                //     which,
-                //     iscapture,
-                // } => todo!(),
-                // Event::RenderTargetsReset { timestamp } => todo!(),
-                // Event::RenderDeviceReset { timestamp } => todo!(),
-                // Event::User {
-                //     timestamp,
-                //     window_id,
-                //     type_,
-                //     code,
-                //     data1,
-                //     data2,
-                // } => todo!(),
-                Event::Unknown { timestamp, type_ } => object! {
-                    "timestamp" = timestamp,
-                    "type" = type_
-                },
-                _ => Value::default()
-            })
+        let frame = self.frames.count.get();
+        let reloaded = self.reuse.as_ref().is_some_and(|reuse| reuse.reloaded.replace(false));
+        let mut replay = self.files.replay.borrow_mut();
+        let event = if reloaded {
+            object! {
+                "kind" = "reload"
+            }
+        } else if let Some(replay) = replay.as_mut() {
+            // the real devices are ignored, but the window still has to be serviced
+            self.pump.pump_events();
+            replay.next(frame).unwrap_or_default()
         } else {
-            Ok(Value::default())
+            self.pump.poll_event().map(event_value).unwrap_or_default()
+        };
+        self.input.update(frame, &event);
+        if event != Value::default() {
+            if let Some(recorder) = self.files.recorder.borrow_mut().as_mut() {
+                recorder.record(frame, &event)?;
+            }
         }
+        Ok(event)
+    }
+    /// services the window while no script is pulling, leaving a replay, the recording and the
+    /// input of `gui` alone. returns whether the window was asked to close
+    pub fn call_idle(&mut self) -> Result<Value, Box<dyn Error>> {
+        let quit = self
+            .pump
+            .poll_iter()
+            .any(|event| matches!(event, Event::Quit { .. }));
+        Ok(Value::Bool(quit))
+    }
+}
+
+pub fn event_value(event: Event) -> Value {
+    match event {
+        Event::Quit { timestamp } => object! {
+            "kind" = "quit",
+            "timestamp" = timestamp
+        },
+        Event::AppTerminating { timestamp } => object! {
+            "kind" = "app_terminated",
+            "timestamp" = timestamp
+        },
+        Event::AppLowMemory { timestamp } => object! {
+            "kind" = "app_low_memory",
+            "timestamp" = timestamp
+        },
+        Event::AppWillEnterBackground { timestamp } => object! {
+            "kind" = "app_will_enter_background",
+            "timestamp" = timestamp
+        },
+        Event::AppDidEnterBackground { timestamp } => object! {
+            "kind" = "app_did_enter_background",
+            "timestamp" = timestamp
+        },
+        Event::AppWillEnterForeground { timestamp } => object! {
+            "kind" = "app_will_enter_foreground",
+            "timestamp" = timestamp
+        },
+        Event::AppDidEnterForeground { timestamp } => object! {
+            "kind" = "app_did_enter_foreground",
+            "timestamp" = timestamp
+        },
+        Event::Display {
+            timestamp,
+            display_index,
+            display_event,
+        } => object! {
+            "kind" = "display",
+            "display_index" = display_index,
+            "display_event" = match display_event {
+                DisplayEvent::None => "none",
+                DisplayEvent::Connected => "connected",
+                DisplayEvent::Disconnected => "disconnected",
+                DisplayEvent::Orientation(orientation) => match orientation {
+                    Orientation::Unknown => "unknown",
+                    Orientation::Landscape => "landscape",
+                    Orientation::LandscapeFlipped => "landscape_flipped",
+                    Orientation::Portrait => "portrait",
+                    Orientation::PortraitFlipped => "portrait_flipped",
+                },
+            },
+            "timestamp" = timestamp
+        },
+        Event::Window {
+            timestamp,
+            window_id,
+            win_event,
+        } => object! {
+            "kind" = "window",
+            "window_id" = window_id,
+            "win_event" = match win_event {
+                WindowEvent::None => "none",
+                WindowEvent::Close => "close",
+                WindowEvent::Leave => "leave",
+                WindowEvent::Maximized => "maximized",
+                WindowEvent::Resized(_, _) => "resized",
+                WindowEvent::Hidden => "hidden",
+                WindowEvent::HitTest => "hit_test",
+                WindowEvent::FocusLost => "focus_lost",
+                WindowEvent::Enter => "enter",
+                WindowEvent::Minimized => "minimized",
+                WindowEvent::Moved(_, _) => "moved",
+                WindowEvent::Shown => "shown",
+                WindowEvent::DisplayChanged(_) => "display_changed",
+                WindowEvent::TakeFocus => "take_focus",
+                WindowEvent::FocusGained => "focus_gained",
+                WindowEvent::Restored => "restored",
+                WindowEvent::SizeChanged(_, _) => "size_changed",
+                WindowEvent::Exposed => "exposed",
+                WindowEvent::ICCProfChanged => "icc_prof_changed",
+            },
+            "width" = if let WindowEvent::Resized(width, _) | WindowEvent::SizeChanged(width, _) | WindowEvent::Moved(width, _) = win_event {
+                Value::Int(width as i64)
+            } else {
+                Value::default()
+            },
+            "height" = if let WindowEvent::Resized(_, height) | WindowEvent::SizeChanged(_, height) | WindowEvent::Moved(_, height) = win_event {
+                Value::Int(height as i64)
+            } else {
+                Value::default()
+            },
+            "timestamp" = timestamp
+        },
+        Event::KeyDown {
+            timestamp,
+            window_id,
+            keycode,
+            scancode,
+            keymod,
+            repeat,
+        } => object! {
+            "kind" = "key_down",
+            "window_id" = window_id,
+            "keycode" = keycode.map(|code| Value::String(code.to_string().to_lowercase())).unwrap_or_default(),
+            "scancode" = scancode.map(|code| Value::String(code.to_string().to_lowercase())).unwrap_or_default(),
+            "keymod" = keymod.to_string().to_lowercase(),
+            "repeat" = repeat,
+            "timestamp" = timestamp
+        },
+        Event::KeyUp {
+            timestamp,
+            window_id,
+            keycode,
+            scancode,
+            keymod,
+            repeat,
+        } => object! {
+            "kind" = "key_up",
+            "window_id" = window_id,
+            "keycode" = keycode.map(|code| Value::String(code.to_string().to_lowercase())).unwrap_or_default(),
+            "scancode" = scancode.map(|code| Value::String(code.to_string().to_lowercase())).unwrap_or_default(),
+            "keymod" = keymod.to_string().to_lowercase(),
+            "repeat" = repeat,
+            "timestamp" = timestamp
+        },
+        Event::TextEditing {
+            timestamp,
+            window_id,
+            text,
+            start,
+            length,
+        } => object! {
+            "kind" = "text_editing",
+            "window_id" = window_id,
+            "text" = text,
+            "start" = start,
+            "length" = length,
+            "timestamp" = timestamp
+        },
+        Event::TextInput {
+            timestamp,
+            window_id,
+            text,
+        } => object! {
+            "kind" = "text_input",
+            "window_id" = window_id,
+            "text" = text,
+            "timestamp" = timestamp
+        },
+        Event::MouseMotion {
+            timestamp,
+            window_id,
+            which,
+            mousestate: _,
+            x,
+            y,
+            xrel,
+            yrel,
+        } => object! {
+            "kind" = "mouse_motion",
+            "window_id" = window_id,
+            "which" = which,
+            "x" = x,
+            "y" = y,
+            "xrel" = xrel,
+            "yrel" = yrel,
+            "timestamp" = timestamp
+        },
+        Event::MouseButtonDown {
+            timestamp,
+            window_id,
+            which,
+            mouse_btn,
+            clicks,
+            x,
+            y,
+        } => object! {
+            "kind" = "mouse_button_down",
+            "window_id" = window_id,
+            "which" = which,
+            "mouse_btn" = mouse_btn as u8,
+            "clicks" = clicks,
+            "x" = x,
+            "y" = y,
+            "timestamp" = timestamp
+        },
+        Event::MouseButtonUp {
+            timestamp,
+            window_id,
+            which,
+            mouse_btn,
+            clicks,
+            x,
+            y,
+        } => object! {
+            "kind" = "mouse_button_up",
+            "window_id" = window_id,
+            "which" = which,
+            "mouse_btn" = mouse_btn as u8,
+            "clicks" = clicks,
+            "x" = x,
+            "y" = y,
+            "timestamp" = timestamp
+        },
+        Event::MouseWheel {
+            timestamp,
+            window_id,
+            which,
+            x,
+            y,
+            direction,
+            precise_x,
+            precise_y,
+        } => object! {
+            "kind" = "mouse_wheel",
+            "window_id" = window_id,
+            "which" = which,
+            "x" = x,
+            "y" = y,
+            "direction" = match direction {
+                MouseWheelDirection::Normal => 0,
+                MouseWheelDirection::Flipped => 1,
+                MouseWheelDirection::Unknown(v) => v,
+            },
+            "precise_x" = precise_x,
+            "precise_y" = precise_y,
+            "timestamp" = timestamp
+        },
+        // Event::JoyAxisMotion {
+        //     timestamp,
+        //     which,
+        //     axis_idx,
+        //     value,
+        // } => todo!(),
+        // Event::JoyBallMotion {
+        //     timestamp,
+        //     which,
+        //     ball_idx,
+        //     xrel,
+        //     yrel,
+        // } => todo!(),
+        // Event::JoyHatMotion {
+        //     timestamp,
+        //     which,
+        //     hat_idx,
+        //     state,
+        // } => todo!(),
+        // Event::JoyButtonDown {
+        //     timestamp,
+        //     which,
+        //     button_idx,
+        // } => todo!(),
+        // Event::JoyButtonUp {
+        //     timestamp,
+        //     which,
+        //     button_idx,
+        // } => todo!(),
+        // Event::JoyDeviceAdded { timestamp, which } => todo!(),
+        // Event::JoyDeviceRemoved { timestamp, which } => todo!(),
+        // Event::ControllerAxisMotion {
+        //     timestamp,
+        //     which,
+        //     axis,
+        //     value,
+        // } => todo!(),
+        // Event::ControllerButtonDown {
+        //     timestamp,
+        //     which,
+        //     button,
+        // } => todo!(),
+        // Event::ControllerButtonUp {
+        //     timestamp,
+        //     which,
+        //     button,
+        // } => todo!(),
+        // Event::ControllerDeviceAdded { timestamp, which } => todo!(),
+        // Event::ControllerDeviceRemoved { timestamp, which } => todo!(),
+        // Event::ControllerDeviceRemapped { timestamp, which } => todo!(),
+        // Event::ControllerTouchpadDown {
+        //     timestamp,
+        //     which,
+        //     touchpad,
+        //     finger,
+        //     x,
+        //     y,
+        //     pressure,
+        // } => todo!(),
+        // Event::ControllerTouchpadMotion {
+        //     timestamp,
+        //     which,
+        //     touchpad,
+        //     finger,
+        //     x,
+        //     y,
+        //     pressure,
+        // } => todo!(),
+        // Event::ControllerTouchpadUp {
+        //     timestamp,
+        //     which,
+        //     touchpad,
+        //     finger,
+        //     x,
+        //     y,
+        //     pressure,
+        // } => todo!(),
+        // Event::FingerDown {
+        //     timestamp,
+        //     touch_id,
+        //     finger_id,
+        //     x,
+        //     y,
+        //     dx,
+        //     dy,
+        //     pressure,
+        // } => todo!(),
+        // Event::FingerUp {
+        //     timestamp,
+        //     touch_id,
+        //     finger_id,
+        //     x,
+        //     y,
+        //     dx,
+        //     dy,
+        //     pressure,
+        // } => todo!(),
+        // Event::FingerMotion {
+        //     timestamp,
+        //     touch_id,
+        //     finger_id,
+        //     x,
+        //     y,
+        //     dx,
+        //     dy,
+        //     pressure,
+        // } => todo!(),
+        // Event::DollarGesture {
+        //     timestamp,
+        //     touch_id,
+        //     gesture_id,
+        //     num_fingers,
+        //     error,
+        //     x,
+        //     y,
+        // } => todo!(),
+        // Event::DollarRecord {
+        //     timestamp,
+        //     touch_id,
+        //     gesture_id,
+        //     num_fingers,
+        //     error,
+        //     x,
+        //     y,
+        // } => todo!(),
+        // Event::MultiGesture {
+        //     timestamp,
+        //     touch_id,
+        //     d_theta,
+        //     d_dist,
+        //     x,
+        //     y,
+        //     num_fingers,
+        // } => todo!(),
+        // Event::ClipboardUpdate { timestamp } => todo!(),
+        // Event::DropFile {
+        //     timestamp,
+        //     window_id,
+        //     filename,
+        // } => todo!(),
+        // Event::DropText {
+        //     timestamp,
+        //     window_id,
+        //     filename,
+        // } => todo!(),
+        // Event::DropBegin {
+        //     timestamp,
+        //     window_id,
+        // } => todo!(),
+        // Event::DropComplete {
+        //     timestamp,
+        //     window_id,
+        // } => todo!(),
+        // Event::AudioDeviceAdded {
+        //     timestamp,
+        //     which,
+        //     iscapture,
+        // } => todo!(),
+        // Event::AudioDeviceRemoved {
+        //     timestamp,
+        //     which,
+        //     iscapture,
+        // } => todo!(),
+        // Event::RenderTargetsReset { timestamp } => todo!(),
+        // Event::RenderDeviceReset { timestamp } => todo!(),
+        // Event::User {
+        //     timestamp,
+        //     window_id,
+        //     type_,
+        //     code,
+        //     data1,
+        //     data2,
+        // } => todo!(),
+        Event::Unknown { timestamp, type_ } => object! {
+            "timestamp" = timestamp,
+            "type" = type_
+        },
+        _ => Value::default()
     }
 }
@@ -0,0 +1,178 @@
+use crate::{
+    interpreter,
+    report::{report, report_trace},
+    require::{Modules, Source},
+    translation::{Options, Reuse},
+};
+use luna_rs::{
+    compile_str,
+    lang::value::Value,
+    luna_impl::{interpreter::Interpreter, position::Located},
+};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs, iter,
+    path::{Path, PathBuf},
+    process::exit,
+    rc::Rc,
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// how many instructions run between checks of the script's modification time
+const STEPS: usize = 4096;
+const CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// runs the script and reruns it in a fresh interpreter every time its file or a module it
+/// required changes.
+/// the sdl context, windows and event pump are reused, so the window stays where it is.
+/// a failing script leaves the window open until the file is fixed
+pub fn watch(path: &str, options: &Options) -> Result<(), Box<dyn Error>> {
+    let reuse = Rc::new(Reuse::default());
+    let options = Options {
+        reuse: Some(Rc::clone(&reuse)),
+        ..options.clone()
+    };
+    let mut times = HashMap::from([(PathBuf::from(path), modified_time(path)?)]);
+    let mut running = load(path, &options);
+    // the modules of the last script that compiled, which are watched as well
+    let mut modules = running
+        .as_ref()
+        .map(|(modules, _, _)| Rc::clone(modules))
+        .unwrap_or_default();
+    let mut last_check = Instant::now();
+    loop {
+        if let Some((modules, text, interpreter)) = &mut running {
+            for _ in 0..STEPS {
+                if interpreter.call_frames.is_empty() {
+                    running = None;
+                    break;
+                }
+                if let Err(Located { value: err, pos }) = interpreter.step() {
+                    report_trace(path, text, err, &pos, interpreter, modules);
+                    running = None;
+                    break;
+                }
+            }
+        } else {
+            idle(&reuse)?;
+            thread::sleep(Duration::from_millis(16));
+        }
+        if last_check.elapsed() >= CHECK_INTERVAL {
+            last_check = Instant::now();
+            if changed(path, &modules, &mut times) {
+                // a script that doesn't compile leaves the previous one running
+                if let Some(loaded) = load(path, &options) {
+                    modules = Rc::clone(&loaded.0);
+                    running = Some(loaded);
+                    reuse.reloaded.set(true);
+                }
+            }
+        }
+    }
+}
+
+fn modified_time(path: impl AsRef<Path>) -> Result<SystemTime, Box<dyn Error>> {
+    Ok(fs::metadata(path)?.modified()?)
+}
+/// updates the modification times of the script and the modules it required, returning whether
+/// any of them changed. modules required for the first time since the last check don't count
+fn changed(path: &str, modules: &Modules, times: &mut HashMap<PathBuf, SystemTime>) -> bool {
+    let mut changed = false;
+    for file in iter::once(PathBuf::from(path)).chain(modules.files()) {
+        // editors may briefly remove the file while saving it
+        let Ok(time) = modified_time(&file) else {
+            continue;
+        };
+        if times.insert(file, time).is_some_and(|old| old != time) {
+            changed = true;
+        }
+    }
+    changed
+}
+/// compiles the script into a fresh interpreter, which also reloads every required module
+fn load(path: &str, options: &Options) -> Option<(Rc<Modules>, String, Interpreter)> {
+    let text = fs::read_to_string(path)
+        .map_err(|err| eprintln!("ERROR: {err}"))
+        .ok()?;
+    let closure = compile_str(&text)
+        .map_err(|Located { value: err, pos }| report(path, &text, err, &pos))
+        .ok()?;
+    let modules = Rc::new(Modules::default());
+    modules.register(&closure, Rc::new(Source::file(path, text.clone())));
+    let options = Options {
+        modules: Rc::clone(&modules),
+        ..options.clone()
+    };
+    Some((modules, text, interpreter(closure, &options)))
+}
+/// services the window while no script is running, so it stays responsive and closable.
+/// a script that opened a window without asking for its events gets them taken from here on
+pub fn idle(reuse: &Reuse) -> Result<(), Box<dyn Error>> {
+    let events = reuse.events.borrow().clone();
+    let events = match events {
+        Some(events) => events,
+        None => {
+            let Some(Value::UserObject(sdl)) = reuse.sdl.borrow().clone() else {
+                return Ok(());
+            };
+            // the event pump is kept in `reuse`, so the script can still get it later
+            let events = sdl.borrow().call("events", vec![])?;
+            events
+        }
+    };
+    let Value::UserObject(events) = events else {
+        return Ok(());
+    };
+    // the events aren't pulled, which would use up a replay and record what nobody saw
+    if events.borrow_mut().call_mut("idle", vec![])? == Value::Bool(true) {
+        exit(0);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use std::fs::File;
+
+    fn touch(path: &Path, secs: u64) {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+        File::options().write(true).open(path).unwrap().set_modified(time).unwrap();
+    }
+
+    #[test]
+    fn notices_changed_modules() {
+        let dir = TempDir::new("watch");
+        dir.write("main.luna", "");
+        dir.write("lib.luna", "");
+        let main = dir.path().join("main.luna");
+        let lib = dir.path().join("lib.luna");
+        let path = main.display().to_string();
+        touch(&main, 1);
+        touch(&lib, 1);
+        let modules = Modules::default();
+        let mut times = HashMap::new();
+        assert!(!changed(&path, &modules, &mut times));
+
+        let options = Options {
+            modules: Rc::new(modules),
+            ..Default::default()
+        };
+        let closure = compile_str("require(\"lib\")").unwrap();
+        let source = Source::file(&path, String::new());
+        options.modules.register(&closure, Rc::new(source));
+        crate::interpreter(closure, &options).run().unwrap();
+        // a module seen for the first time isn't a change
+        assert!(!changed(&path, &options.modules, &mut times));
+        touch(&lib, 2);
+        assert!(changed(&path, &options.modules, &mut times));
+        assert!(!changed(&path, &options.modules, &mut times));
+        touch(&main, 2);
+        assert!(changed(&path, &options.modules, &mut times));
+        fs::remove_file(&lib).unwrap();
+        assert!(!changed(&path, &options.modules, &mut times));
+    }
+}